@@ -0,0 +1,37 @@
+use std::fmt::Display;
+
+/// An error returned by a command of the mtc CLI. The `Display` output is the message shown to the user so other
+/// interfaces can show the same messages or react to specific failures by matching the variants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// The given item type doesn't exist.
+    UnknownType(String),
+    /// A required argument was not given.
+    MissingArg(String),
+    /// No item matches the given id or body.
+    NotFound(String),
+    /// An argument couldn't be parsed to the expected value.
+    Parse(String),
+    /// An argument was recognized but not valid in this context.
+    Invalid(String),
+    /// Reading the sync config or syncing with the server failed.
+    Sync(String),
+    /// Setting up the terminal or accessing a file failed.
+    Io(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownType(typ) => write!(f, "Unknown type: '{}'", typ),
+            CommandError::MissingArg(msg)
+            | CommandError::NotFound(msg)
+            | CommandError::Parse(msg)
+            | CommandError::Invalid(msg)
+            | CommandError::Sync(msg)
+            | CommandError::Io(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
@@ -1,34 +1,67 @@
-use crate::{ItemState, MtcItem};
+use crate::{HasUuid, ItemState, Keyed, MtcItem};
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use uuid::Uuid;
 
-/// A short-term task that should be done on a optionally given weekday.
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+/// A short-term task that should be done on a optionally given weekday or repeatedly every few days.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Todo {
     date: NaiveDate,
     body: String,
     state: ItemState,
     id: usize,
+    #[serde(default)]
+    interval_days: Option<u32>,
+    #[serde(default)]
+    inbox: bool,
+    #[serde(default)]
+    order: i32,
+    #[serde(default)]
+    context: Option<String>,
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
+    #[serde(default)]
+    scheduled: Option<bool>,
 }
 
 /// A repeating task with a duration in minutes for a optionally given weekday.
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     weekdays: [bool; 7],
     body: String,
     duration: u32,
     state: ItemState,
     id: usize,
+    #[serde(default)]
+    order: i32,
+    #[serde(default)]
+    done_dates: Vec<NaiveDate>,
+    #[serde(default)]
+    context: Option<String>,
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
 }
 
 /// An event that will happen on a given date.
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     date: NaiveDate,
     body: String,
     state: ItemState,
     id: usize,
+    #[serde(default)]
+    order: i32,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    time: Option<(NaiveTime, NaiveTime)>,
+    #[serde(default)]
+    yearly: bool,
+    #[serde(default)]
+    since_year: Option<i32>,
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
 }
 
 fn get_date_from_wd(weekday: Option<Weekday>) -> NaiveDate {
@@ -53,6 +86,12 @@ impl Todo {
             body,
             state: ItemState::Neutral,
             id: 0,
+            interval_days: None,
+            inbox: false,
+            order: 0,
+            context: None,
+            uuid: Uuid::new_v4(),
+            scheduled: Some(weekday.is_some()),
         }
     }
 
@@ -63,6 +102,12 @@ impl Todo {
             body,
             state: ItemState::Neutral,
             id: 0,
+            interval_days: None,
+            inbox: false,
+            order: 0,
+            context: None,
+            uuid: Uuid::new_v4(),
+            scheduled: Some(true),
         }
     }
 
@@ -71,19 +116,106 @@ impl Todo {
         &self.body
     }
 
+    /// Sets the body of the `Todo`.
+    pub fn set_body(&mut self, body: String) {
+        self.body = body;
+    }
+
     /// Returns the optionally specified weekday of the `Todo`.
     pub fn date(&self) -> NaiveDate {
         self.date
     }
 
-    /// Sets the optional weekday of the `Todo`.
+    /// Sets the `Todo` to be for an explicit date.
+    pub fn set_date(&mut self, date: NaiveDate) {
+        self.date = date;
+        self.scheduled = Some(true);
+    }
+
+    /// Sets the optional weekday of the `Todo`. This clears the interval since a `Todo` cannot have both.
     pub fn set_weekday(&mut self, new_weekday: Option<Weekday>) {
         self.date = get_date_from_wd(new_weekday);
+        self.interval_days = None;
+        self.scheduled = Some(new_weekday.is_some());
+    }
+
+    /// Returns the weekday the `Todo` is for if it is for a single upcoming day. Todos for any day, todos in the
+    /// inbox, repeating todos and todos whose day has passed aren't for a weekday even though they are shown on some.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::Todo;
+    ///
+    /// let todo = Todo::new("Buy milk".to_string(), Some(Weekday::Mon));
+    /// assert_eq!(todo.weekday(), Some(Weekday::Mon));
+    ///
+    /// let todo = Todo::new("Buy milk".to_string(), None);
+    /// assert_eq!(todo.weekday(), None);
+    /// ```
+    pub fn weekday(&self) -> Option<Weekday> {
+        let upcoming = self.date >= Local::today().naive_local();
+        if self.is_scheduled() && !self.inbox && self.interval_days.is_none() && upcoming {
+            Some(self.date.weekday())
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the `Todo` was given a weekday or a date. Other todos are for any day and their date is
+    /// only the day they were created on. Todos saved before this was tracked are scheduled for their date, as all
+    /// todos were then, so that they still have a weekday and expire.
+    pub fn is_scheduled(&self) -> bool {
+        self.scheduled.unwrap_or(true)
+    }
+
+    /// Returns the interval in days in which the `Todo` repeats if it has one.
+    pub fn interval_days(&self) -> Option<u32> {
+        self.interval_days
+    }
+
+    /// Sets the `Todo` to repeat every given amount of days starting from its date. The date acts as
+    /// the anchor for the interval.
+    pub fn set_interval_days(&mut self, interval_days: Option<u32>) {
+        self.interval_days = interval_days;
+    }
+
+    /// Returns true if the `Todo` is in the inbox, meaning that it was quickly captured and hasn't
+    /// been given a day yet.
+    pub fn is_inbox(&self) -> bool {
+        self.inbox
+    }
+
+    /// Sets whether the `Todo` is in the inbox.
+    pub fn set_inbox(&mut self, inbox: bool) {
+        self.inbox = inbox;
+    }
+
+    /// Returns the manual display order of the `Todo`. Items with a lower order are shown first.
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    /// Sets the manual display order of the `Todo`. Items with a lower order are shown first.
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+
+    /// Returns the context of the `Todo`, such as `home` or `computer`, if it has one.
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// Sets or clears the context of the `Todo`.
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
     }
 }
 
 impl Task {
     /// Creates a new `Task` with a given body, duration in minutes and optionally a weekday.
+    /// The duration should be at least one minute. The CLI refuses shorter durations but the value isn't enforced here.
     pub fn new(body: String, duration: u32, weekday: Option<Weekday>) -> Task {
         let mut weekdays: [bool; 7] = [false, false, false, false, false, false, false];
 
@@ -97,6 +229,10 @@ impl Task {
             duration,
             state: ItemState::Neutral,
             id: 0,
+            order: 0,
+            done_dates: Vec::new(),
+            context: None,
+            uuid: Uuid::new_v4(),
         }
     }
 
@@ -105,15 +241,25 @@ impl Task {
         &self.body
     }
 
+    /// Sets the body of the `Task`.
+    pub fn set_body(&mut self, body: String) {
+        self.body = body;
+    }
+
     /// Returns the duration of the `Task`.
     pub fn duration(&self) -> u32 {
         self.duration
     }
 
+    /// Sets the duration of the `Task` in minutes.
+    pub fn set_duration(&mut self, duration: u32) {
+        self.duration = duration;
+    }
+
     /// Returns a array defining all the weekdays this task is for. 0th element indicates monday.
     /// A value of `true` indicates that a task is for the day.
     pub fn weekdays(&self) -> [bool; 7] {
-        self.weekdays.clone()
+        self.weekdays
     }
 
     /// Sets the array defining all the weekdays this task is for. 0th element indicates monday.
@@ -139,16 +285,60 @@ impl Task {
     pub fn set_for_weekday(&mut self, weekday: Weekday, is_for: bool) {
         self.weekdays[(weekday.number_from_monday() - 1) as usize] = is_for;
     }
+
+    /// Returns the manual display order of the `Task`. Items with a lower order are shown first.
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    /// Sets the manual display order of the `Task`. Items with a lower order are shown first.
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+
+    /// Returns the dates the `Task` has been done on, oldest first.
+    pub fn done_dates(&self) -> &[NaiveDate] {
+        &self.done_dates
+    }
+
+    /// Marks the `Task` done on a date. The `Task` is still for its other dates like before.
+    pub fn mark_done(&mut self, date: NaiveDate) {
+        if !self.done_dates.contains(&date) {
+            self.done_dates.push(date);
+            self.done_dates.sort();
+        }
+    }
+
+    /// Marks the `Task` not done on a date.
+    pub fn unmark_done(&mut self, date: NaiveDate) {
+        self.done_dates.retain(|d| *d != date);
+    }
+
+    /// Returns the context of the `Task`, such as `home` or `computer`, if it has one.
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// Sets or clears the context of the `Task`.
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
+    }
 }
 
 impl Event {
-    /// Creates a new `Event` with a given body, date and optionally a time.
+    /// Creates a new `Event` with a given body and date. The `Event` lasts the whole day until a time is set.
     pub fn new(body: String, date: NaiveDate) -> Event {
         Event {
             body,
             date,
             state: ItemState::Neutral,
             id: 0,
+            order: 0,
+            location: None,
+            time: None,
+            yearly: false,
+            since_year: None,
+            uuid: Uuid::new_v4(),
         }
     }
 
@@ -157,6 +347,11 @@ impl Event {
         &self.body
     }
 
+    /// Sets the body of the `Event`.
+    pub fn set_body(&mut self, body: String) {
+        self.body = body;
+    }
+
     /// Returns the date of the `Event`.
     pub fn date(&self) -> NaiveDate {
         self.date
@@ -166,17 +361,388 @@ impl Event {
     pub fn set_date(&mut self, new_date: NaiveDate) {
         self.date = new_date;
     }
+
+    /// Returns the manual display order of the `Event`. Items with a lower order are shown first.
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    /// Sets the manual display order of the `Event`. Items with a lower order are shown first.
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+
+    /// Returns the location of the `Event` if it has one.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Sets or clears the location of the `Event`.
+    pub fn set_location(&mut self, location: Option<String>) {
+        self.location = location;
+    }
+
+    /// Returns the start and end times of the `Event` if it has them. `None` means that the `Event` lasts the
+    /// whole day.
+    pub fn time(&self) -> Option<(NaiveTime, NaiveTime)> {
+        self.time
+    }
+
+    /// Sets or clears the start and end times of the `Event`. The start is inclusive and the end exclusive.
+    pub fn set_time(&mut self, time: Option<(NaiveTime, NaiveTime)>) {
+        self.time = time;
+    }
+
+    /// Returns true if the `Event` repeats every year on the month and day of its date.
+    pub fn is_yearly(&self) -> bool {
+        self.yearly
+    }
+
+    /// Sets whether the `Event` repeats every year. A yearly `Event` starts from its date and never expires.
+    pub fn set_yearly(&mut self, yearly: bool) {
+        self.yearly = yearly;
+    }
+
+    /// Returns the year a yearly `Event` such as a birthday is counted from if it has one.
+    pub fn since_year(&self) -> Option<i32> {
+        self.since_year
+    }
+
+    /// Sets or clears the year a yearly `Event` is counted from.
+    pub fn set_since_year(&mut self, since_year: Option<i32>) {
+        self.since_year = since_year;
+    }
+
+    /// Returns how many years have passed from the since year to the year of the given date.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::EventBuilder;
+    ///
+    /// let birthday = EventBuilder::new("Birthday".to_string(), NaiveDate::from_ymd(2022, 5, 1))
+    ///     .since_year(1992)
+    ///     .build();
+    ///
+    /// assert_eq!(birthday.years_on(NaiveDate::from_ymd(2023, 5, 1)), Some(31));
+    /// ```
+    pub fn years_on(&self, date: NaiveDate) -> Option<i32> {
+        self.since_year.map(|year| date.year() - year)
+    }
+
+    /// Returns true if the date of the `Event` has passed but it hasn't expired yet, meaning that it may have been
+    /// missed. Yearly events are never overdue since they happen again.
+    pub fn is_overdue(&self) -> bool {
+        !self.yearly && self.date < Local::today().naive_local() && !self.expired()
+    }
+
+    /// Returns the date of a yearly `Event` in the given year. An `Event` on February 29th is on February 28th in
+    /// other than leap years.
+    fn date_in_year(&self, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, self.date.month(), self.date.day())
+            .unwrap_or_else(|| NaiveDate::from_ymd(year, 2, 28))
+    }
+}
+
+/// A builder for creating `Todo`s with optional fields set fluently.
+///
+/// # Example
+///
+/// ```
+/// use chrono::prelude::*;
+/// use mtc::{Todo, TodoBuilder};
+///
+/// let todo = TodoBuilder::new("Buy milk".to_string())
+///     .weekday(Weekday::Mon)
+///     .build();
+///
+/// assert_eq!(todo, Todo::new("Buy milk".to_string(), Some(Weekday::Mon)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TodoBuilder {
+    body: String,
+    date: NaiveDate,
+    scheduled: bool,
+    interval_days: Option<u32>,
+    inbox: bool,
+    order: i32,
+    context: Option<String>,
+}
+
+impl TodoBuilder {
+    /// Creates a new `TodoBuilder` for a `Todo` with the given body. By default the `Todo` is for today.
+    pub fn new(body: String) -> TodoBuilder {
+        TodoBuilder {
+            body,
+            date: get_date_from_wd(None),
+            scheduled: false,
+            interval_days: None,
+            inbox: false,
+            order: 0,
+            context: None,
+        }
+    }
+
+    /// Sets the `Todo` to be for the next given weekday.
+    pub fn weekday(mut self, weekday: Weekday) -> TodoBuilder {
+        self.date = get_date_from_wd(Some(weekday));
+        self.scheduled = true;
+        self
+    }
+
+    /// Sets the `Todo` to be for an explicit date.
+    pub fn date(mut self, date: NaiveDate) -> TodoBuilder {
+        self.date = date;
+        self.scheduled = true;
+        self
+    }
+
+    /// Sets the `Todo` to repeat every given amount of days starting from its date.
+    pub fn interval_days(mut self, interval_days: u32) -> TodoBuilder {
+        self.interval_days = Some(interval_days);
+        self
+    }
+
+    /// Sets the manual display order of the `Todo`.
+    pub fn order(mut self, order: i32) -> TodoBuilder {
+        self.order = order;
+        self
+    }
+
+    /// Puts the `Todo` in the inbox.
+    pub fn inbox(mut self) -> TodoBuilder {
+        self.inbox = true;
+        self
+    }
+
+    /// Sets the context of the `Todo`.
+    pub fn context(mut self, context: String) -> TodoBuilder {
+        self.context = Some(context);
+        self
+    }
+
+    /// Creates the `Todo`.
+    pub fn build(self) -> Todo {
+        let mut todo = Todo::new_dated(self.body, self.date);
+        todo.scheduled = Some(self.scheduled);
+        todo.set_interval_days(self.interval_days);
+        todo.set_inbox(self.inbox);
+        todo.set_order(self.order);
+        todo.set_context(self.context);
+        todo
+    }
+}
+
+/// A builder for creating `Task`s with optional fields set fluently.
+///
+/// # Example
+///
+/// ```
+/// use chrono::prelude::*;
+/// use mtc::{Task, TaskBuilder};
+///
+/// let task = TaskBuilder::new("Exercise".to_string(), 60)
+///     .weekday(Weekday::Mon)
+///     .weekday(Weekday::Fri)
+///     .build();
+///
+/// assert!(task.is_for_weekday(Weekday::Mon));
+/// assert!(task.is_for_weekday(Weekday::Fri));
+/// assert!(!task.is_for_weekday(Weekday::Tue));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TaskBuilder {
+    body: String,
+    duration: u32,
+    weekdays: [bool; 7],
+    order: i32,
+    context: Option<String>,
+}
+
+impl TaskBuilder {
+    /// Creates a new `TaskBuilder` for a `Task` with the given body and duration in minutes. By
+    /// default the `Task` is for every day.
+    pub fn new(body: String, duration: u32) -> TaskBuilder {
+        TaskBuilder {
+            body,
+            duration,
+            weekdays: [false; 7],
+            order: 0,
+            context: None,
+        }
+    }
+
+    /// Adds a weekday the `Task` is for. Can be called multiple times.
+    pub fn weekday(mut self, weekday: Weekday) -> TaskBuilder {
+        self.weekdays[(weekday.number_from_monday() - 1) as usize] = true;
+        self
+    }
+
+    /// Sets the array defining all the weekdays the `Task` is for. 0th element indicates monday.
+    pub fn weekdays(mut self, weekdays: [bool; 7]) -> TaskBuilder {
+        self.weekdays = weekdays;
+        self
+    }
+
+    /// Sets the manual display order of the `Task`.
+    pub fn order(mut self, order: i32) -> TaskBuilder {
+        self.order = order;
+        self
+    }
+
+    /// Sets the context of the `Task`.
+    pub fn context(mut self, context: String) -> TaskBuilder {
+        self.context = Some(context);
+        self
+    }
+
+    /// Creates the `Task`.
+    pub fn build(self) -> Task {
+        let mut task = Task::new(self.body, self.duration, None);
+        task.set_weekdays(self.weekdays);
+        task.set_order(self.order);
+        task.set_context(self.context);
+        task
+    }
+}
+
+/// A builder for creating `Event`s with optional fields set fluently.
+///
+/// # Example
+///
+/// ```
+/// use chrono::prelude::*;
+/// use mtc::{Event, EventBuilder};
+///
+/// let event = EventBuilder::new("Dentist".to_string(), NaiveDate::from_ymd(2022, 1, 5)).build();
+///
+/// assert_eq!(event, Event::new("Dentist".to_string(), NaiveDate::from_ymd(2022, 1, 5)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventBuilder {
+    body: String,
+    date: NaiveDate,
+    order: i32,
+    location: Option<String>,
+    time: Option<(NaiveTime, NaiveTime)>,
+    yearly: bool,
+    since_year: Option<i32>,
+}
+
+impl EventBuilder {
+    /// Creates a new `EventBuilder` for an `Event` with the given body and date.
+    pub fn new(body: String, date: NaiveDate) -> EventBuilder {
+        EventBuilder {
+            body,
+            date,
+            order: 0,
+            location: None,
+            time: None,
+            yearly: false,
+            since_year: None,
+        }
+    }
+
+    /// Sets the date of the `Event`.
+    pub fn date(mut self, date: NaiveDate) -> EventBuilder {
+        self.date = date;
+        self
+    }
+
+    /// Sets the manual display order of the `Event`.
+    pub fn order(mut self, order: i32) -> EventBuilder {
+        self.order = order;
+        self
+    }
+
+    /// Sets the location of the `Event`.
+    pub fn location(mut self, location: String) -> EventBuilder {
+        self.location = Some(location);
+        self
+    }
+
+    /// Sets the start and end times of the `Event`.
+    pub fn time(mut self, start: NaiveTime, end: NaiveTime) -> EventBuilder {
+        self.time = Some((start, end));
+        self
+    }
+
+    /// Makes the `Event` repeat every year.
+    pub fn yearly(mut self) -> EventBuilder {
+        self.yearly = true;
+        self
+    }
+
+    /// Sets the year the `Event` is counted from, such as the birth year of a birthday. This also makes the
+    /// `Event` repeat every year.
+    pub fn since_year(mut self, year: i32) -> EventBuilder {
+        self.yearly = true;
+        self.since_year = Some(year);
+        self
+    }
+
+    /// Creates the `Event`.
+    pub fn build(self) -> Event {
+        let mut event = Event::new(self.body, self.date);
+        event.set_order(self.order);
+        event.set_location(self.location);
+        event.set_time(self.time);
+        event.set_yearly(self.yearly);
+        event.set_since_year(self.since_year);
+        event
+    }
+}
+
+impl HasUuid for Todo {
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+impl Keyed for Todo {
+    type Key = Uuid;
+
+    /// Items are keyed by their uuid.
+    fn key(&self) -> Uuid {
+        self.uuid
+    }
 }
 
 impl MtcItem for Todo {
     /// Returns true if the `Todo` is for a given date. A `Todo` is for a given date if the date is today, and today is after the `Todo`s date.
+    /// This means that a `Todo` that wasn't removed on its day carries over to today until it is removed.
+    /// A `Todo` with an interval is for every date that is a multiple of the interval away from its date.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{MtcItem, TodoBuilder};
+    ///
+    /// let todo = TodoBuilder::new("Water plants".to_string())
+    ///     .date(NaiveDate::from_ymd(2022, 1, 1))
+    ///     .interval_days(3)
+    ///     .build();
+    ///
+    /// assert!(todo.for_date(NaiveDate::from_ymd(2022, 1, 7)));
+    /// assert!(!todo.for_date(NaiveDate::from_ymd(2022, 1, 8)));
+    /// ```
     fn for_date(&self, date: NaiveDate) -> bool {
+        if let Some(interval) = self.interval_days {
+            let days = date.signed_duration_since(self.date).num_days();
+            return interval > 0 && days >= 0 && days % interval as i64 == 0;
+        }
         if self.date < date {
             date == Local::today().naive_local()
         } else {
             date.weekday() == self.date.weekday()
         }
     }
+    /// Returns true if the `Todo` repeats every few days. A `Todo` for a weekday is only for the next date of it.
+    fn is_recurring(&self) -> bool {
+        self.interval_days.is_some()
+    }
     fn state(&self) -> ItemState {
         self.state
     }
@@ -202,14 +768,48 @@ impl MtcItem for Todo {
     fn ignore_state_eq(&self, other: &Todo) -> bool {
         self.body == other.body && self.date == other.date
     }
+    fn body(&self) -> &str {
+        &self.body
+    }
     fn id(&self) -> usize {
         self.id
     }
     fn set_id(&mut self, new_id: usize) {
         self.id = new_id;
     }
+    /// Returns true if the weekday or the date the `Todo` was for has passed. Since undone todos are shown for
+    /// today, the CLI only removes expired todos when it is configured to do so. Repeating todos, todos in the inbox
+    /// and todos for any day never expire since their date is only the day they were created on. Todos saved before
+    /// this was tracked expire by their date, see `Todo::is_scheduled`.
     fn expired(&self) -> bool {
-        false
+        self.is_scheduled()
+            && !self.inbox
+            && self.interval_days.is_none()
+            && self.date < Local::today().naive_local()
+    }
+    /// Checks that the body isn't empty and that the interval is at least one day.
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = validate_body(&self.body);
+        if self.interval_days == Some(0) {
+            errors.push("The interval must be at least one day.".to_string());
+        }
+        errors.extend(validate_context(self.context.as_deref()));
+        into_result(errors)
+    }
+}
+
+impl HasUuid for Task {
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+impl Keyed for Task {
+    type Key = Uuid;
+
+    /// Items are keyed by their uuid.
+    fn key(&self) -> Uuid {
+        self.uuid
     }
 }
 
@@ -231,6 +831,27 @@ impl MtcItem for Task {
     fn for_date(&self, date: NaiveDate) -> bool {
         self.is_for_weekday(date.weekday())
     }
+    /// Returns true if the `Task` has been marked done on the date with `mark_done`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{Task, MtcItem};
+    ///
+    /// let mut item = Task::new("Exercise".to_string(), 60, None);
+    /// item.mark_done(NaiveDate::from_ymd(2021, 12, 6));
+    ///
+    /// assert!(item.is_done_on(NaiveDate::from_ymd(2021, 12, 6)));
+    /// assert!(!item.is_done_on(NaiveDate::from_ymd(2021, 12, 7)));
+    /// ```
+    fn is_done_on(&self, date: NaiveDate) -> bool {
+        self.done_dates.contains(&date)
+    }
+    /// Returns true since a `Task` repeats on its weekdays or every day if it has none.
+    fn is_recurring(&self) -> bool {
+        true
+    }
     fn state(&self) -> ItemState {
         self.state
     }
@@ -258,6 +879,9 @@ impl MtcItem for Task {
             && self.weekdays == other.weekdays
             && self.duration == other.duration
     }
+    fn body(&self) -> &str {
+        &self.body
+    }
     fn id(&self) -> usize {
         self.id
     }
@@ -267,6 +891,30 @@ impl MtcItem for Task {
     fn expired(&self) -> bool {
         false
     }
+    /// Checks that the body isn't empty and that the duration is at least one minute.
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = validate_body(&self.body);
+        if self.duration == 0 {
+            errors.push("The duration must be at least one minute.".to_string());
+        }
+        errors.extend(validate_context(self.context.as_deref()));
+        into_result(errors)
+    }
+}
+
+impl HasUuid for Event {
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+impl Keyed for Event {
+    type Key = Uuid;
+
+    /// Items are keyed by their uuid.
+    fn key(&self) -> Uuid {
+        self.uuid
+    }
 }
 
 impl MtcItem for Event {
@@ -284,7 +932,58 @@ impl MtcItem for Event {
     /// assert!(!item.for_date(NaiveDate::from_ymd(2021, 12, 6)));
     /// ```
     fn for_date(&self, date: NaiveDate) -> bool {
-        self.date == date
+        if self.yearly {
+            date >= self.date && date == self.date_in_year(date.year())
+        } else {
+            self.date == date
+        }
+    }
+    /// Returns true if the `Event` is yearly.
+    fn is_recurring(&self) -> bool {
+        self.yearly
+    }
+    /// Returns a copy of a yearly `Event` moved to the given date if it is for it so that the date and the years are
+    /// shown for that year.
+    fn occurrence_on(&self, date: NaiveDate) -> Self {
+        let mut event = self.clone();
+        if self.yearly && self.for_date(date) {
+            event.date = date;
+        }
+        event
+    }
+    /// Returns true if the `Event` is on the date of the given instant and, if it has a time, the instant is between
+    /// the start and end times.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{EventBuilder, MtcItem};
+    ///
+    /// let date = NaiveDate::from_ymd(2022, 1, 5);
+    /// let item = EventBuilder::new("Meeting".to_string(), date)
+    ///     .time(NaiveTime::from_hms(10, 0, 0), NaiveTime::from_hms(11, 0, 0))
+    ///     .build();
+    ///
+    /// assert!(item.for_datetime(date.and_hms(10, 30, 0)));
+    /// assert!(!item.for_datetime(date.and_hms(11, 0, 0)));
+    /// ```
+    fn for_datetime(&self, datetime: NaiveDateTime) -> bool {
+        self.for_date(datetime.date())
+            && self.time.map_or(true, |(start, end)| {
+                start <= datetime.time() && datetime.time() < end
+            })
+    }
+    /// Returns true if the date of the `Event` is on the given weekday. A yearly `Event` is on a different weekday
+    /// every year so the weekday of its next occurrence from today is used.
+    fn for_weekday_recurring(&self, weekday: Weekday) -> bool {
+        let date = if self.yearly {
+            self.next_occurrence(Local::today().naive_local())
+                .unwrap_or(self.date)
+        } else {
+            self.date
+        };
+        date.weekday() == weekday
     }
     fn state(&self) -> ItemState {
         self.state
@@ -312,97 +1011,411 @@ impl MtcItem for Event {
     fn ignore_state_eq(&self, other: &Self) -> bool {
         self.body == other.body && self.date == other.date
     }
+    fn body(&self) -> &str {
+        &self.body
+    }
     fn id(&self) -> usize {
         self.id
     }
     fn set_id(&mut self, new_id: usize) {
         self.id = new_id;
     }
+    /// Returns true if the `Event` was over three days ago. Yearly events never expire.
     fn expired(&self) -> bool {
         let today = Local::today().naive_local();
-        return self.date.signed_duration_since(today).num_days() < -3;
+        !self.yearly && self.date.signed_duration_since(today).num_days() < -3
+    }
+    /// Checks that the body isn't empty and that the date is at most `EVENT_MAX_YEARS` years away from today.
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = validate_body(&self.body);
+        let today = Local::today().naive_local();
+        if (self.date.year() - today.year()).abs() > EVENT_MAX_YEARS {
+            errors.push(format!(
+                "The date must be at most {} years from today.",
+                EVENT_MAX_YEARS
+            ));
+        }
+        if self
+            .location
+            .as_ref()
+            .map_or(false, |l| l.trim().is_empty())
+        {
+            errors.push("The location must not be empty.".to_string());
+        }
+        if self.time.map_or(false, |(start, end)| start >= end) {
+            errors.push("The start time must be before the end time.".to_string());
+        }
+        if let Some(year) = self.since_year {
+            if !self.yearly {
+                errors.push("Only yearly events can have a since year.".to_string());
+            }
+            if year > self.date.year() {
+                errors.push("The since year must not be after the date.".to_string());
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// How many years in the past or future an `Event` can be before `validate` considers its date a mistake.
+pub const EVENT_MAX_YEARS: i32 = 100;
+
+fn validate_context(context: Option<&str>) -> Option<String> {
+    if context.map_or(false, |c| {
+        c.trim().is_empty() || c.contains(char::is_whitespace)
+    }) {
+        Some("The context must be a single word.".to_string())
+    } else {
+        None
+    }
+}
+
+fn validate_body(body: &str) -> Vec<String> {
+    if body.trim().is_empty() {
+        vec!["The body must not be empty.".to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+fn into_result(errors: Vec<String>) -> Result<(), Vec<String>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
 }
 
 impl Ord for Todo {
+    /// Orders by the manual order first and by the body second.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.body.cmp(&other.body)
+        self.order
+            .cmp(&other.order)
+            .then_with(|| self.body.cmp(&other.body))
     }
 }
 
 impl PartialOrd for Todo {
     fn partial_cmp(&self, other: &Self) -> std::option::Option<std::cmp::Ordering> {
-        Some(self.body.cmp(&other.body))
+        Some(self.cmp(other))
+    }
+}
+
+// The uuid is left out so that items are equal by their content, id and state like before they had uuids.
+impl PartialEq for Todo {
+    fn eq(&self, other: &Self) -> bool {
+        self.date == other.date
+            && self.body == other.body
+            && self.state == other.state
+            && self.id == other.id
+            && self.interval_days == other.interval_days
+            && self.inbox == other.inbox
+            && self.order == other.order
+            && self.context == other.context
+            && self.scheduled == other.scheduled
     }
 }
 
 impl Eq for Todo {}
 
 impl Ord for Task {
+    /// Orders by the manual order first and by the body second.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.body.cmp(&other.body)
+        self.order
+            .cmp(&other.order)
+            .then_with(|| self.body.cmp(&other.body))
     }
 }
 
 impl PartialOrd for Task {
     fn partial_cmp(&self, other: &Self) -> std::option::Option<std::cmp::Ordering> {
-        Some(self.body.cmp(&other.body))
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.weekdays == other.weekdays
+            && self.body == other.body
+            && self.duration == other.duration
+            && self.state == other.state
+            && self.id == other.id
+            && self.order == other.order
+            && self.done_dates == other.done_dates
+            && self.context == other.context
     }
 }
 
 impl Eq for Task {}
 
 impl Ord for Event {
+    /// Orders by the date first, by the time within a date second, by the manual order third and by the body last.
+    /// Events lasting the whole day come before timed ones.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let order = self.date.cmp(&other.date);
-        if order == std::cmp::Ordering::Equal {
-            self.body.cmp(&other.body)
-        } else {
-            order
-        }
+        self.date
+            .cmp(&other.date)
+            .then_with(|| self.time.cmp(&other.time))
+            .then_with(|| self.order.cmp(&other.order))
+            .then_with(|| self.body.cmp(&other.body))
     }
 }
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> std::option::Option<std::cmp::Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.date == other.date
+            && self.body == other.body
+            && self.state == other.state
+            && self.id == other.id
+            && self.order == other.order
+            && self.location == other.location
+            && self.time == other.time
+            && self.yearly == other.yearly
+            && self.since_year == other.since_year
     }
 }
 
 impl Eq for Event {}
 
 impl Display for Todo {
+    /// Formats the `Todo`. The context is shown after the body as `@context`. The alternate flag (`{:#}`) leaves out
+    /// the id.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "{} (ID: {})", self.body, self.id)
+        write!(f, "{}", self.body)?;
+        if let Some(context) = &self.context {
+            write!(f, " @{}", context)?;
+        }
+        if !f.alternate() {
+            write!(f, " (ID: {})", self.id)?;
+        }
+        Ok(())
     }
 }
 
 impl Display for Task {
+    /// Formats the `Task`. The weekdays are shown after the duration, or `daily` if the task is for every day, and
+    /// the context last as `@context`. The alternate flag (`{:#}`) leaves out the weekdays and the id.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "{}: {} minutes (ID: {})",
-            self.body, self.duration, self.id
-        )
+        write!(f, "{}: {} minutes", self.body, self.duration)?;
+        if f.alternate() {
+            if let Some(context) = &self.context {
+                write!(f, " @{}", context)?;
+            }
+            return Ok(());
+        }
+
+        let mut weekdays = Vec::new();
+        let mut weekday = Weekday::Mon;
+        for is_for in self.weekdays {
+            if is_for {
+                weekdays.push(weekday.to_string());
+            }
+            weekday = weekday.succ();
+        }
+        if weekdays.is_empty() || weekdays.len() == 7 {
+            write!(f, ", daily")?;
+        } else {
+            write!(f, ", {}", weekdays.join(","))?;
+        }
+        if let Some(context) = &self.context {
+            write!(f, " @{}", context)?;
+        }
+
+        write!(f, " (ID: {})", self.id)
     }
 }
 
 impl Display for Event {
+    /// Formats the `Event`. The time is shown after the weekday as `HH:MM-HH:MM` and the location after the body as
+    /// `@ location`. Yearly events show `(yearly)` after the body or, if they have a since year, the years at the
+    /// date such as `(turns 30)`. The alternate flag (`{:#}`) leaves out the id.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "{} {}: {} (ID: {})",
-            self.date,
-            self.date.weekday(),
-            self.body,
-            self.id
-        )
+        write!(f, "{} {}", self.date, self.date.weekday())?;
+        if let Some((start, end)) = self.time {
+            write!(f, " {}-{}", start.format("%H:%M"), end.format("%H:%M"))?;
+        }
+        write!(f, ": {}", self.body)?;
+        if let Some(years) = self.years_on(self.date) {
+            write!(f, " (turns {})", years)?;
+        } else if self.yearly {
+            write!(f, " (yearly)")?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " @ {}", location)?;
+        }
+        if !f.alternate() {
+            write!(f, " (ID: {})", self.id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Any of the item types, for storing different types in one list or stream. It is serialized with the type as a
+/// `type` field next to the fields of the item, such as `{"type":"todo","body":"Todo",...}`. The type names are
+/// part of the saved format so they don't change between versions.
+///
+/// # Example
+/// ```
+/// use mtc::{AnyMtcItem, MtcItem, Todo};
+///
+/// let item = AnyMtcItem::from(Todo::new("Todo".to_string(), None));
+/// let json = serde_json::to_string(&item).unwrap();
+///
+/// assert!(json.starts_with(r#"{"type":"todo","#));
+/// assert_eq!(serde_json::from_str::<AnyMtcItem>(&json).unwrap(), item);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AnyMtcItem {
+    /// A `Todo`.
+    #[serde(rename = "todo")]
+    Todo(Todo),
+    /// A `Task`.
+    #[serde(rename = "task")]
+    Task(Task),
+    /// A `Event`.
+    #[serde(rename = "event")]
+    Event(Event),
+}
+
+impl AnyMtcItem {
+    /// Returns the name of the type of the item which is also the `type` field when serialized.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            AnyMtcItem::Todo(_) => "todo",
+            AnyMtcItem::Task(_) => "task",
+            AnyMtcItem::Event(_) => "event",
+        }
+    }
+}
+
+impl From<Todo> for AnyMtcItem {
+    fn from(todo: Todo) -> AnyMtcItem {
+        AnyMtcItem::Todo(todo)
+    }
+}
+
+impl From<Task> for AnyMtcItem {
+    fn from(task: Task) -> AnyMtcItem {
+        AnyMtcItem::Task(task)
+    }
+}
+
+impl From<Event> for AnyMtcItem {
+    fn from(event: Event) -> AnyMtcItem {
+        AnyMtcItem::Event(event)
+    }
+}
+
+/// Calls the same expression for the item inside any variant of `AnyMtcItem`.
+macro_rules! each_variant {
+    ($any:expr, $item:ident => $body:expr) => {
+        match $any {
+            AnyMtcItem::Todo($item) => $body,
+            AnyMtcItem::Task($item) => $body,
+            AnyMtcItem::Event($item) => $body,
+        }
+    };
+}
+
+impl MtcItem for AnyMtcItem {
+    fn for_date(&self, date: NaiveDate) -> bool {
+        each_variant!(self, item => item.for_date(date))
+    }
+    fn is_done_on(&self, date: NaiveDate) -> bool {
+        each_variant!(self, item => item.is_done_on(date))
+    }
+    fn is_recurring(&self) -> bool {
+        each_variant!(self, item => item.is_recurring())
+    }
+    fn occurrence_on(&self, date: NaiveDate) -> Self {
+        each_variant!(self, item => item.occurrence_on(date).into())
+    }
+    fn for_datetime(&self, datetime: NaiveDateTime) -> bool {
+        each_variant!(self, item => item.for_datetime(datetime))
+    }
+    fn for_weekday_recurring(&self, weekday: Weekday) -> bool {
+        each_variant!(self, item => item.for_weekday_recurring(weekday))
+    }
+    fn state(&self) -> ItemState {
+        each_variant!(self, item => item.state())
+    }
+    fn set_state(&mut self, new_state: ItemState) {
+        each_variant!(self, item => item.set_state(new_state))
+    }
+    /// Returns true if both are of the same type and equal excluding the `ItemState`.
+    fn ignore_state_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AnyMtcItem::Todo(a), AnyMtcItem::Todo(b)) => a.ignore_state_eq(b),
+            (AnyMtcItem::Task(a), AnyMtcItem::Task(b)) => a.ignore_state_eq(b),
+            (AnyMtcItem::Event(a), AnyMtcItem::Event(b)) => a.ignore_state_eq(b),
+            _ => false,
+        }
+    }
+    fn body(&self) -> &str {
+        each_variant!(self, item => MtcItem::body(item))
+    }
+    fn id(&self) -> usize {
+        each_variant!(self, item => item.id())
+    }
+    fn set_id(&mut self, new_id: usize) {
+        each_variant!(self, item => item.set_id(new_id))
+    }
+    fn expired(&self) -> bool {
+        each_variant!(self, item => item.expired())
+    }
+    fn validate(&self) -> Result<(), Vec<String>> {
+        each_variant!(self, item => item.validate())
+    }
+}
+
+impl HasUuid for AnyMtcItem {
+    fn uuid(&self) -> Uuid {
+        each_variant!(self, item => item.uuid())
+    }
+}
+
+impl Display for AnyMtcItem {
+    /// Formats the item like the type inside does.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        each_variant!(self, item => Display::fmt(item, f))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::MtcList;
+
+    #[test]
+    fn todo_builder_builds_expected() {
+        let date = NaiveDate::from_ymd(2022, 1, 5);
+        let todo = TodoBuilder::new("Todo".to_string()).date(date).build();
+        assert_eq!(todo, Todo::new_dated("Todo".to_string(), date));
+
+        let todo = TodoBuilder::new("Todo".to_string()).build();
+        assert_eq!(todo, Todo::new("Todo".to_string(), None));
+    }
+
+    #[test]
+    fn task_builder_builds_expected() {
+        let task = TaskBuilder::new("Task".to_string(), 30).build();
+        assert_eq!(task, Task::new("Task".to_string(), 30, None));
+
+        let task = TaskBuilder::new("Task".to_string(), 30)
+            .weekday(Weekday::Tue)
+            .weekday(Weekday::Sun)
+            .build();
+        assert_eq!(
+            task.weekdays(),
+            [false, true, false, false, false, false, true]
+        );
+    }
 
     #[test]
     fn todo_sets_correct_date_from_wd() {
@@ -430,6 +1443,16 @@ mod tests {
         assert!(ti.for_date(date));
     }
 
+    #[test]
+    fn todo_for_past_date_carries_over_to_today() {
+        let today = Local::today().naive_local();
+        let todo = Todo::new_dated("test".to_string(), today.pred().pred());
+
+        assert!(todo.for_date(today));
+        assert!(!todo.for_date(today.pred()));
+        assert!(!todo.for_date(today.succ()));
+    }
+
     #[test]
     fn todo_for_date_returns_false() {
         let date = Local::today().naive_local();
@@ -456,6 +1479,63 @@ mod tests {
         assert!(todo.for_weekday(weekday));
     }
 
+    #[test]
+    fn todo_with_interval_for_date_works() {
+        let anchor = NaiveDate::from_ymd(2021, 12, 30);
+        let mut todo = Todo::new_dated("test".to_string(), anchor);
+        todo.set_interval_days(Some(3));
+
+        assert!(todo.for_date(anchor));
+        assert!(todo.for_date(NaiveDate::from_ymd(2022, 1, 2)));
+        assert!(todo.for_date(NaiveDate::from_ymd(2022, 1, 5)));
+        assert!(!todo.for_date(NaiveDate::from_ymd(2022, 1, 3)));
+        assert!(!todo.for_date(NaiveDate::from_ymd(2021, 12, 27)));
+    }
+
+    #[test]
+    fn todo_interval_ignored_in_ignore_state_eq() {
+        let mut todo = Todo::new("test".to_string(), None);
+        todo.set_interval_days(Some(3));
+
+        assert!(todo.ignore_state_eq(&Todo::new("test".to_string(), None)));
+    }
+
+    #[test]
+    fn task_next_occurrence_weekly_works() {
+        let task = Task::new("Task".to_string(), 10, Some(Weekday::Mon));
+
+        // 2021.12.6 was a monday.
+        let monday = NaiveDate::from_ymd(2021, 12, 6);
+        assert_eq!(task.next_occurrence(monday), Some(monday));
+        assert_eq!(
+            task.next_occurrence(monday.succ()),
+            Some(NaiveDate::from_ymd(2021, 12, 13))
+        );
+    }
+
+    #[test]
+    fn task_next_occurrence_every_day_works() {
+        let mut task = Task::new("Task".to_string(), 10, None);
+        task.set_weekdays([true; 7]);
+
+        let date = NaiveDate::from_ymd(2021, 12, 9);
+        assert_eq!(task.next_occurrence(date), Some(date));
+    }
+
+    #[test]
+    fn event_next_occurrence_one_off_works() {
+        let event = Event::new("Event".to_string(), NaiveDate::from_ymd(2021, 12, 24));
+
+        assert_eq!(
+            event.next_occurrence(NaiveDate::from_ymd(2021, 12, 1)),
+            Some(NaiveDate::from_ymd(2021, 12, 24))
+        );
+        assert_eq!(
+            event.next_occurrence(NaiveDate::from_ymd(2021, 12, 25)),
+            None
+        );
+    }
+
     #[test]
     fn task_for_date_returns_true() {
         let date = NaiveDate::from_ymd(2021, 12, 6);
@@ -488,6 +1568,45 @@ mod tests {
         assert!(!ti.for_date(date));
     }
 
+    #[test]
+    fn event_for_datetime_within_time_returns_true() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let ti = EventBuilder::new("test".to_string(), date)
+            .time(
+                NaiveTime::from_hms(10, 0, 0),
+                NaiveTime::from_hms(11, 30, 0),
+            )
+            .build();
+
+        assert!(ti.for_datetime(date.and_hms(10, 0, 0)));
+        assert!(ti.for_datetime(date.and_hms(11, 29, 59)));
+    }
+
+    #[test]
+    fn event_for_datetime_outside_time_returns_false() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let ti = EventBuilder::new("test".to_string(), date)
+            .time(
+                NaiveTime::from_hms(10, 0, 0),
+                NaiveTime::from_hms(11, 30, 0),
+            )
+            .build();
+
+        assert!(!ti.for_datetime(date.and_hms(9, 59, 59)));
+        assert!(!ti.for_datetime(date.and_hms(11, 30, 0)));
+        assert!(!ti.for_datetime(date.succ().and_hms(10, 30, 0)));
+    }
+
+    #[test]
+    fn event_for_datetime_without_time_matches_whole_day() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let ti = Event::new("test".to_string(), date);
+
+        assert!(ti.for_datetime(date.and_hms(0, 0, 0)));
+        assert!(ti.for_datetime(date.and_hms(23, 59, 59)));
+        assert!(!ti.for_datetime(date.pred().and_hms(12, 0, 0)));
+    }
+
     #[test]
     fn todo_ignore_state_eq_returns_true() {
         let mut item1 = Todo::new("Task 1".to_string(), None);
@@ -502,7 +1621,7 @@ mod tests {
 
     #[test]
     fn todo_ignore_state_eq_returns_false() {
-        let mut item1 = Todo::new("Task 1".to_string(), Some(Weekday::Fri));
+        let mut item1 = Todo::new("Task 1".to_string(), Some(Local::today().weekday().succ()));
         item1.set_state(ItemState::New);
 
         let mut item2 = Todo::new("Task 1".to_string(), None);
@@ -512,6 +1631,30 @@ mod tests {
         assert!(!item2.ignore_state_eq(&item1));
     }
 
+    #[test]
+    fn task_done_dates_are_kept_out_of_ignore_state_eq() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let mut item1 = Task::new("Task 1".to_string(), 30, None);
+        item1.mark_done(date);
+        item1.mark_done(date);
+        item1.mark_done(date.pred());
+
+        assert_eq!(item1.done_dates(), &[date.pred(), date]);
+        assert!(item1.is_done_on(date));
+        assert!(item1.for_date(date.succ()));
+        assert!(item1.ignore_state_eq(&Task::new("Task 1".to_string(), 30, None)));
+
+        item1.unmark_done(date);
+        assert!(!item1.is_done_on(date));
+    }
+
+    #[test]
+    fn task_without_done_dates_deserializes() {
+        let json = r#"{"weekdays":[false,false,false,false,false,false,false],"body":"Task 1","duration":30,"state":"Neutral","id":0}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert!(task.done_dates().is_empty());
+    }
+
     #[test]
     fn task_ignore_state_eq_returns_true() {
         let mut item1 = Task::new("Task 1".to_string(), 30, None);
@@ -583,22 +1726,252 @@ mod tests {
         assert_eq!(events, expected);
     }
 
+    #[test]
+    fn todo_order_uses_manual_order_first() {
+        let mut pinned = Todo::new("B".to_string(), None);
+        pinned.set_order(-1);
+
+        let mut todos = vec![
+            Todo::new("C".to_string(), None),
+            pinned.clone(),
+            Todo::new("A".to_string(), None),
+        ];
+        todos.sort();
+
+        let expected = vec![
+            pinned,
+            Todo::new("A".to_string(), None),
+            Todo::new("C".to_string(), None),
+        ];
+        assert_eq!(todos, expected);
+    }
+
+    #[test]
+    fn event_order_uses_manual_order_within_date() {
+        let mut pinned = Event::new("2 Event".to_string(), NaiveDate::from_ymd(2022, 1, 2));
+        pinned.set_order(-1);
+
+        let mut events = vec![
+            Event::new("1 Event".to_string(), NaiveDate::from_ymd(2022, 1, 2)),
+            pinned.clone(),
+            Event::new("3 Event".to_string(), NaiveDate::from_ymd(2022, 1, 1)),
+        ];
+        events.sort();
+
+        let expected = vec![
+            Event::new("3 Event".to_string(), NaiveDate::from_ymd(2022, 1, 1)),
+            pinned.clone(),
+            Event::new("1 Event".to_string(), NaiveDate::from_ymd(2022, 1, 2)),
+        ];
+        assert_eq!(events, expected);
+        assert!(pinned.ignore_state_eq(&Event::new(
+            "2 Event".to_string(),
+            NaiveDate::from_ymd(2022, 1, 2)
+        )));
+    }
+
+    #[test]
+    fn validate_accepts_valid_items() {
+        assert_eq!(Todo::new("Todo".to_string(), None).validate(), Ok(()));
+        assert_eq!(Task::new("Task".to_string(), 10, None).validate(), Ok(()));
+        let event = Event::new("Event".to_string(), Local::today().naive_local());
+        assert_eq!(event.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_returns_every_invalid_field() {
+        let todo = TodoBuilder::new(" ".to_string()).interval_days(0).build();
+        assert_eq!(todo.validate().unwrap_err().len(), 2);
+
+        let task = Task::new("".to_string(), 0, None);
+        assert_eq!(
+            task.validate(),
+            Err(vec![
+                "The body must not be empty.".to_string(),
+                "The duration must be at least one minute.".to_string()
+            ])
+        );
+
+        let event = EventBuilder::new("Event".to_string(), NaiveDate::from_ymd(1800, 1, 1))
+            .location("".to_string())
+            .build();
+        assert_eq!(event.validate().unwrap_err().len(), 2);
+    }
+
     #[test]
     fn todo_item_display_works() {
         let todo_item = Todo::new("Do Task 1".to_string(), Some(Weekday::Mon));
         assert_eq!(format!("{}", todo_item), "Do Task 1 (ID: 0)");
+        assert_eq!(format!("{:#}", todo_item), "Do Task 1");
     }
 
     #[test]
     fn task_display_works() {
         let task = Task::new("Do Task 1".to_string(), 10, Some(Weekday::Mon));
-        assert_eq!(format!("{}", task), "Do Task 1: 10 minutes (ID: 0)");
+        assert_eq!(format!("{}", task), "Do Task 1: 10 minutes, Mon (ID: 0)");
+        assert_eq!(format!("{:#}", task), "Do Task 1: 10 minutes");
+    }
+
+    #[test]
+    fn task_display_shows_weekdays() {
+        let task = TaskBuilder::new("Task".to_string(), 10)
+            .weekday(Weekday::Mon)
+            .weekday(Weekday::Wed)
+            .weekday(Weekday::Fri)
+            .build();
+        assert_eq!(format!("{}", task), "Task: 10 minutes, Mon,Wed,Fri (ID: 0)");
+
+        let daily = Task::new("Task".to_string(), 10, None);
+        assert_eq!(format!("{}", daily), "Task: 10 minutes, daily (ID: 0)");
+
+        let every_day = TaskBuilder::new("Task".to_string(), 10)
+            .weekdays([true; 7])
+            .build();
+        assert_eq!(format!("{}", every_day), "Task: 10 minutes, daily (ID: 0)");
     }
 
     #[test]
     fn event_display_works() {
         let event = Event::new("Event 1".to_string(), NaiveDate::from_ymd(2021, 1, 5));
         assert_eq!(format!("{}", event), "2021-01-05 Tue: Event 1 (ID: 0)");
+        assert_eq!(format!("{:#}", event), "2021-01-05 Tue: Event 1");
+    }
+
+    #[test]
+    fn event_display_shows_location() {
+        let event = EventBuilder::new("Event 1".to_string(), NaiveDate::from_ymd(2021, 1, 5))
+            .location("Room 101".to_string())
+            .build();
+        assert_eq!(
+            format!("{}", event),
+            "2021-01-05 Tue: Event 1 @ Room 101 (ID: 0)"
+        );
+        assert_eq!(format!("{:#}", event), "2021-01-05 Tue: Event 1 @ Room 101");
+    }
+
+    #[test]
+    fn event_display_shows_time() {
+        let event = EventBuilder::new("Event 1".to_string(), NaiveDate::from_ymd(2021, 1, 5))
+            .time(NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(10, 15, 0))
+            .build();
+        assert_eq!(
+            format!("{}", event),
+            "2021-01-05 Tue 09:00-10:15: Event 1 (ID: 0)"
+        );
+    }
+
+    #[test]
+    fn todo_and_task_display_show_context() {
+        let todo = TodoBuilder::new("Todo 1".to_string())
+            .context("home".to_string())
+            .build();
+        assert_eq!(format!("{}", todo), "Todo 1 @home (ID: 0)");
+        assert_eq!(format!("{:#}", todo), "Todo 1 @home");
+
+        let task = TaskBuilder::new("Task 1".to_string(), 30)
+            .weekday(Weekday::Mon)
+            .context("computer".to_string())
+            .build();
+        assert_eq!(
+            format!("{}", task),
+            "Task 1: 30 minutes, Mon @computer (ID: 0)"
+        );
+        assert_eq!(format!("{:#}", task), "Task 1: 30 minutes @computer");
+    }
+
+    #[test]
+    fn context_ignored_in_ignore_state_eq() {
+        let mut todo = Todo::new("Todo 1".to_string(), None);
+        todo.set_context(Some("home".to_string()));
+        assert!(todo.ignore_state_eq(&Todo::new("Todo 1".to_string(), None)));
+
+        let mut task = Task::new("Task 1".to_string(), 30, None);
+        task.set_context(Some("home".to_string()));
+        assert!(task.ignore_state_eq(&Task::new("Task 1".to_string(), 30, None)));
+    }
+
+    #[test]
+    fn todo_without_context_deserializes() {
+        let json = r#"{"date":"2021-01-05","body":"Todo 1","state":"Neutral","id":0}"#;
+        let todo: Todo = serde_json::from_str(json).unwrap();
+        assert_eq!(todo.context(), None);
+    }
+
+    #[test]
+    fn context_must_be_one_word() {
+        let mut todo = Todo::new("Todo 1".to_string(), None);
+        todo.set_context(Some("at home".to_string()));
+        assert!(todo.validate().is_err());
+        todo.set_context(Some("home".to_string()));
+        assert!(todo.validate().is_ok());
+    }
+
+    #[test]
+    fn event_location_ignored_in_ignore_state_eq() {
+        let date = NaiveDate::from_ymd(2021, 1, 5);
+        let mut event = Event::new("Event 1".to_string(), date);
+        event.set_location(Some("Room 101".to_string()));
+        assert!(event.ignore_state_eq(&Event::new("Event 1".to_string(), date)));
+    }
+
+    #[test]
+    fn event_without_location_deserializes() {
+        let json = r#"{"date":"2021-01-05","body":"Event 1","state":"Neutral","id":0}"#;
+        let event: Event = serde_json::from_str(json).unwrap();
+        assert_eq!(event.location(), None);
+    }
+
+    #[test]
+    fn todo_is_expired_works() {
+        let today = Local::today().naive_local();
+        let todo = Todo::new_dated("Todo 1".to_string(), today);
+        assert!(!todo.expired());
+
+        let todo = Todo::new_dated("Todo 1".to_string(), today.succ());
+        assert!(!todo.expired());
+
+        let todo = Todo::new_dated("Todo 1".to_string(), today.pred());
+        assert!(todo.expired());
+
+        let todo = Todo::new("Todo 1".to_string(), Some(today.weekday().pred()));
+        assert!(!todo.expired());
+    }
+
+    #[test]
+    fn todo_for_any_day_or_in_inbox_is_not_expired() {
+        let yesterday = Local::today().naive_local().pred();
+
+        // The date of a todo for any day is the day it was created on.
+        let mut todo = Todo::new("Todo 1".to_string(), None);
+        todo.date = yesterday;
+        assert!(!todo.is_scheduled());
+        assert!(!todo.expired());
+
+        let mut todo = TodoBuilder::new("Todo 1".to_string()).inbox().build();
+        todo.date = yesterday;
+        assert!(!todo.expired());
+
+        let mut todo = Todo::new("Todo 1".to_string(), None);
+        todo.set_date(yesterday);
+        assert!(todo.is_scheduled());
+        assert!(todo.expired());
+    }
+
+    #[test]
+    fn todo_saved_before_scheduled_existed_is_for_its_date() {
+        let json = r#"{"date":"2021-12-06","body":"Todo 1","state":"Neutral","id":0}"#;
+        let todo: Todo = serde_json::from_str(json).unwrap();
+        assert!(todo.is_scheduled());
+        assert!(todo.expired());
+
+        let date = Local::today().naive_local() + chrono::Duration::days(2);
+        let json = format!(
+            r#"{{"date":"{}","body":"Todo 1","state":"Neutral","id":0}}"#,
+            date
+        );
+        let todo: Todo = serde_json::from_str(&json).unwrap();
+        assert_eq!(todo.weekday(), Some(date.weekday()));
+        assert!(!todo.expired());
     }
 
     #[test]
@@ -619,4 +1992,199 @@ mod tests {
         let event = Event::new("Event 1".to_string(), today.pred().pred().pred().pred());
         assert!(event.expired());
     }
+
+    #[test]
+    fn any_mtc_item_round_trips_every_type() {
+        let items = vec![
+            AnyMtcItem::from(
+                TodoBuilder::new("Todo".to_string())
+                    .context("home".to_string())
+                    .build(),
+            ),
+            AnyMtcItem::from(Task::new("Task".to_string(), 30, Some(Weekday::Mon))),
+            AnyMtcItem::from(
+                EventBuilder::new("Event".to_string(), NaiveDate::from_ymd(2022, 1, 5))
+                    .time(NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(10, 0, 0))
+                    .build(),
+            ),
+        ];
+
+        for item in items {
+            let json = serde_json::to_value(&item).unwrap();
+            assert_eq!(json["type"], item.type_name());
+            assert_eq!(serde_json::from_value::<AnyMtcItem>(json).unwrap(), item);
+        }
+    }
+
+    #[test]
+    fn any_mtc_item_reads_tagged_json() {
+        let json = r#"{"type":"task","weekdays":[true,false,false,false,false,false,false],"body":"Task","duration":30,"state":"Neutral","id":0}"#;
+        let item: AnyMtcItem = serde_json::from_str(json).unwrap();
+
+        assert_eq!(item.type_name(), "task");
+        assert!(item.for_date(NaiveDate::from_ymd(2021, 12, 6)));
+        assert!(serde_json::from_str::<AnyMtcItem>(r#"{"type":"note","body":"Note"}"#).is_err());
+    }
+
+    #[test]
+    fn any_mtc_item_list_holds_every_type() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let mut list = MtcList::new(false);
+        list.add(AnyMtcItem::from(Todo::new_dated("Todo".to_string(), date)));
+        list.add(AnyMtcItem::from(Task::new(
+            "Task".to_string(),
+            30,
+            Some(Weekday::Tue),
+        )));
+        list.add(AnyMtcItem::from(Event::new("Event".to_string(), date)));
+
+        let bodies: Vec<&str> = list.items_for_date(date).iter().map(|i| i.body()).collect();
+        assert_eq!(bodies, vec!["Todo", "Event"]);
+        assert!(!list.items()[0].ignore_state_eq(list.items()[2]));
+        assert_eq!(
+            format!("{}", list.items()[1]),
+            "Task: 30 minutes, Tue (ID: 1)"
+        );
+    }
+
+    #[test]
+    fn yearly_event_for_date_matches_every_year_from_its_date() {
+        let event = EventBuilder::new("Birthday".to_string(), NaiveDate::from_ymd(2021, 5, 1))
+            .yearly()
+            .build();
+
+        assert!(event.for_date(NaiveDate::from_ymd(2021, 5, 1)));
+        assert!(event.for_date(NaiveDate::from_ymd(2030, 5, 1)));
+        assert!(!event.for_date(NaiveDate::from_ymd(2030, 5, 2)));
+        assert!(!event.for_date(NaiveDate::from_ymd(2020, 5, 1)));
+        assert_eq!(
+            event.next_occurrence(NaiveDate::from_ymd(2021, 5, 2)),
+            Some(NaiveDate::from_ymd(2022, 5, 1))
+        );
+        assert!(!event.expired());
+    }
+
+    #[test]
+    fn yearly_event_is_for_the_weekday_of_its_next_occurrence() {
+        let next = Local::today().naive_local() + chrono::Duration::days(7);
+        // Four years back is a leap year too and on a different weekday.
+        let original = NaiveDate::from_ymd(next.year() - 4, next.month(), next.day());
+        let event = EventBuilder::new("Birthday".to_string(), original)
+            .yearly()
+            .build();
+
+        assert!(event.for_weekday_recurring(next.weekday()));
+        assert!(!event.for_weekday_recurring(original.weekday()));
+    }
+
+    #[test]
+    fn yearly_event_on_leap_day_is_on_february_28th_in_other_years() {
+        let event = EventBuilder::new("Birthday".to_string(), NaiveDate::from_ymd(2020, 2, 29))
+            .yearly()
+            .build();
+
+        assert!(event.for_date(NaiveDate::from_ymd(2021, 2, 28)));
+        assert!(!event.for_date(NaiveDate::from_ymd(2024, 2, 28)));
+        assert!(event.for_date(NaiveDate::from_ymd(2024, 2, 29)));
+    }
+
+    #[test]
+    fn yearly_event_display_shows_years_of_occurrence() {
+        let event = EventBuilder::new(
+            "Alice's birthday".to_string(),
+            NaiveDate::from_ymd(2021, 5, 1),
+        )
+        .since_year(1992)
+        .build();
+        assert_eq!(
+            format!("{:#}", event),
+            "2021-05-01 Sat: Alice's birthday (turns 29)"
+        );
+
+        let occurrence = event.occurrence_on(NaiveDate::from_ymd(2022, 5, 1));
+        assert_eq!(
+            format!("{:#}", occurrence),
+            "2022-05-01 Sun: Alice's birthday (turns 30)"
+        );
+        // Dates the event isn't for leave it as it is.
+        assert_eq!(event.occurrence_on(NaiveDate::from_ymd(2022, 5, 2)), event);
+
+        let event = EventBuilder::new("Anniversary".to_string(), NaiveDate::from_ymd(2021, 5, 1))
+            .yearly()
+            .build();
+        assert_eq!(
+            format!("{:#}", event),
+            "2021-05-01 Sat: Anniversary (yearly)"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_invalid_since_year() {
+        let mut event = Event::new("Birthday".to_string(), NaiveDate::from_ymd(2021, 5, 1));
+        event.set_since_year(Some(1992));
+        assert_eq!(
+            event.validate(),
+            Err(vec!["Only yearly events can have a since year.".to_string()])
+        );
+
+        event.set_yearly(true);
+        assert_eq!(event.validate(), Ok(()));
+
+        event.set_since_year(Some(2022));
+        assert_eq!(
+            event.validate(),
+            Err(vec![
+                "The since year must not be after the date.".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn is_recurring_for_todos() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        assert!(!Todo::new_dated("Shop".to_string(), date).is_recurring());
+        assert!(!Todo::new("Shop".to_string(), Some(Weekday::Mon)).is_recurring());
+        assert!(TodoBuilder::new("Water plants".to_string())
+            .date(date)
+            .interval_days(3)
+            .build()
+            .is_recurring());
+    }
+
+    #[test]
+    fn is_recurring_for_tasks() {
+        assert!(Task::new("Exercise".to_string(), 60, Some(Weekday::Mon)).is_recurring());
+        // Tasks without weekdays are for every day.
+        assert!(Task::new("Exercise".to_string(), 60, None).is_recurring());
+    }
+
+    #[test]
+    fn is_recurring_for_events() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        assert!(!Event::new("Meeting".to_string(), date).is_recurring());
+        let yearly = EventBuilder::new("Birthday".to_string(), date)
+            .yearly()
+            .build();
+        assert!(yearly.is_recurring());
+        assert!(AnyMtcItem::from(yearly).is_recurring());
+        assert!(!AnyMtcItem::from(Todo::new_dated("Shop".to_string(), date)).is_recurring());
+    }
+
+    #[test]
+    fn event_is_overdue_between_its_date_and_expiring() {
+        let today = Local::today().naive_local();
+        let event =
+            |days: i64| Event::new("Meeting".to_string(), today + chrono::Duration::days(days));
+
+        assert!(!event(0).is_overdue());
+        assert!(event(-1).is_overdue());
+        assert!(event(-3).is_overdue());
+        assert!(!event(-4).is_overdue());
+        assert!(
+            !EventBuilder::new("Birthday".to_string(), today - chrono::Duration::days(1))
+                .yearly()
+                .build()
+                .is_overdue()
+        );
+    }
 }
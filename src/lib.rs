@@ -1,19 +1,29 @@
 //! This API provides the base functionality for mtc. It can be also used for creating apps that
 //! can sync with the mtc CLI app or serve as an additional interface.
+//!
+//! Syncing with a server over SSH requires the `remote` feature, which is enabled by default. Disabling default
+//! features leaves only the item types and [`MtcList`] which don't need ssh2 or OpenSSL.
 
 #![warn(missing_docs)]
 
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::iter::FromIterator;
 
+mod error;
 mod items;
 
+pub use crate::error::*;
 pub use crate::items::*;
 
+#[cfg(feature = "remote")]
 mod remote;
 
+#[cfg(feature = "remote")]
 pub use crate::remote::*;
 
+pub use uuid::Uuid;
+
 /// An Item for `MtcList`. A struct implementing `MtcItem` is usually defined for a specific time and it has a `ItemState`.
 pub trait MtcItem {
     /// Returns true if the item is for a given date.
@@ -43,7 +53,8 @@ pub trait MtcItem {
     /// assert!(!WeekdayItem { weekday: Weekday::Mon }.for_date(NaiveDate::from_ymd(2021, 12, 5)));
     /// ```
     fn for_date(&self, date: NaiveDate) -> bool;
-    /// Returns true if the item is for today.
+    /// Returns true if the item is for today. Today is the current date in the local timezone which can
+    /// differ between synced machines around midnight. Use `for_date` with an explicit date to pin it.
     ///
     /// # Example
     /// ```
@@ -71,6 +82,29 @@ pub trait MtcItem {
     fn for_today(&self) -> bool {
         self.for_date(Local::today().naive_local())
     }
+    /// Returns true if the item has been checked off for a given date while still being for other dates. Only
+    /// repeating items can be done without removing them so this is false by default.
+    fn is_done_on(&self, _date: NaiveDate) -> bool {
+        false
+    }
+    /// Returns true if the item repeats instead of being for a single date, for example to show a repeat icon next to
+    /// it. Items are one-off by default.
+    fn is_recurring(&self) -> bool {
+        false
+    }
+    /// Returns the item as it is on a given date. Only items repeating with their date differ from the item itself, so
+    /// by default this is a copy of the item.
+    fn occurrence_on(&self, _date: NaiveDate) -> Self
+    where
+        Self: Sized + Clone,
+    {
+        self.clone()
+    }
+    /// Returns true if the item is happening at a given instant. Items without a time are for their whole date so by
+    /// default this is the same as `for_date` with the date of the instant.
+    fn for_datetime(&self, datetime: NaiveDateTime) -> bool {
+        self.for_date(datetime.date())
+    }
     /// Returns true if the item is for a given weekday.
     ///
     /// # Example
@@ -104,6 +138,116 @@ pub trait MtcItem {
         }
         self.for_date(weekday_date)
     }
+    /// Returns true if the item is for a given weekday regardless of which date of that weekday.
+    /// By default this is the same as `for_weekday` which is correct for items repeating weekly.
+    /// Items bound to a single date should override this to compare the weekday of that date.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{Event, MtcItem};
+    ///
+    /// // 2021.12.6 was a monday.
+    /// let event = Event::new("Event".to_string(), NaiveDate::from_ymd(2021, 12, 6));
+    ///
+    /// assert!(event.for_weekday_recurring(Weekday::Mon));
+    /// assert!(!event.for_weekday_recurring(Weekday::Tue));
+    /// ```
+    fn for_weekday_recurring(&self, weekday: Weekday) -> bool {
+        self.for_weekday(weekday)
+    }
+    /// Returns true if the item matches all the criteria of the `Query`.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{MtcItem, Query, Task};
+    ///
+    /// let item = Task::new("Exercise".to_string(), 60, Some(Weekday::Mon));
+    ///
+    /// let query = Query {
+    ///     text: Some("exer".to_string()),
+    ///     date: Some(NaiveDate::from_ymd(2021, 12, 6)),
+    ///     ..Query::default()
+    /// };
+    ///
+    /// assert!(item.matches(&query));
+    /// ```
+    fn matches(&self, query: &Query) -> bool {
+        query.text.as_ref().map_or(true, |text| {
+            self.body().to_lowercase().contains(&text.to_lowercase())
+        }) && query.date.map_or(true, |date| self.for_date(date))
+            && query.state.map_or(true, |state| self.state() == state)
+    }
+    /// Returns the first date starting from `from` (inclusive) the item is for. Only the next year
+    /// is scanned so `None` is returned for items that don't occur again, like past events.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{MtcItem, Task};
+    ///
+    /// let task = Task::new("Exercise".to_string(), 60, Some(Weekday::Mon));
+    ///
+    /// // 2021.12.8 was a wednesday.
+    /// assert_eq!(
+    ///     task.next_occurrence(NaiveDate::from_ymd(2021, 12, 8)),
+    ///     Some(NaiveDate::from_ymd(2021, 12, 13))
+    /// );
+    /// ```
+    fn next_occurrence(&self, from: NaiveDate) -> Option<NaiveDate> {
+        from.iter_days().take(366).find(|date| self.for_date(*date))
+    }
+    /// Returns the item with its state set to the given state. Useful for building items for
+    /// comparisons.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::prelude::*;
+    /// use mtc::{ItemState, MtcItem, Todo};
+    ///
+    /// let todo = Todo::new("Todo".to_string(), None).with_state(ItemState::Removed);
+    ///
+    /// assert_eq!(todo.state(), ItemState::Removed);
+    /// ```
+    fn with_state(mut self, state: ItemState) -> Self
+    where
+        Self: Sized,
+    {
+        self.set_state(state);
+        self
+    }
+    /// Returns the id and the body of the item as a line of exactly `width` characters for aligned listings. The id
+    /// is padded to a column of 5 characters and a body that doesn't fit is cut at a character boundary and ends
+    /// with `…`.
+    ///
+    /// # Example
+    /// ```
+    /// use mtc::{MtcItem, Todo};
+    ///
+    /// let todo = Todo::new("Buy groceries".to_string(), None);
+    ///
+    /// assert_eq!(todo.summary_line(12), "0    Buy gr…");
+    /// assert_eq!(todo.summary_line(20), "0    Buy groceries  ");
+    /// ```
+    fn summary_line(&self, width: usize) -> String {
+        let line = format!("{:<5}{}", self.id(), self.body().replace('\n', " "));
+        let len = line.chars().count();
+        if len <= width {
+            format!("{}{}", line, " ".repeat(width - len))
+        } else if width == 0 {
+            String::new()
+        } else {
+            let mut cut: String = line.chars().take(width - 1).collect();
+            cut.push('…');
+            cut
+        }
+    }
+    /// Checks that the fields of the item are sensible, for example before saving an item from a form, and returns a
+    /// message for every invalid field. Items are valid by default.
+    fn validate(&self) -> Result<(), Vec<String>> {
+        Ok(())
+    }
     /// Returns the `ItemState` of the item.
     fn state(&self) -> ItemState;
     /// Sets the `ItemState` of the item.
@@ -148,6 +292,11 @@ pub trait MtcItem {
     /// assert!(item1.ignore_state_eq(&item2));
     /// ```
     fn ignore_state_eq(&self, other: &Self) -> bool;
+    /// Returns the body of the item. Lookups and listings by body such as `MtcList::get_all_by_body` use this so
+    /// items with a body should override it. Items are without a body by default.
+    fn body(&self) -> &str {
+        ""
+    }
     /// Gets the id of the item.
     fn id(&self) -> usize;
     /// Sets the id of the items. `MtcList` usually handles setting the id so in most cases calling this manually is not needed nor recommended.
@@ -156,6 +305,46 @@ pub trait MtcItem {
     fn expired(&self) -> bool;
 }
 
+/// An item with a `Uuid` that stays the same when the item is synced or its id changes. Edits should change a copy
+/// of the item so that the copy keeps the `Uuid`.
+///
+/// Items saved before they had a `Uuid` get a new random one every time they are read until they are saved again.
+/// The CLI saves the items after every command so this only lasts until the first command.
+pub trait HasUuid {
+    /// Returns the `Uuid` of the item.
+    fn uuid(&self) -> Uuid;
+}
+
+/// An item that can be found by a key of its own choosing. Unlike the positional id of a `MtcItem`,
+/// the key should stay the same when a `MtcList` is synced so it can be a string or a `Uuid` for example.
+/// The id is still used by the CLI since numbers are easier to type.
+///
+/// # Example
+/// ```
+/// use mtc::Keyed;
+///
+/// struct Note {
+///     slug: String,
+/// }
+///
+/// impl Keyed for Note {
+///     type Key = String;
+///
+///     fn key(&self) -> String {
+///         self.slug.clone()
+///     }
+/// }
+///
+/// assert_eq!(Note { slug: "groceries".to_string() }.key(), "groceries");
+/// ```
+pub trait Keyed {
+    /// The type of the key.
+    type Key: PartialEq;
+
+    /// Returns the key of the item.
+    fn key(&self) -> Self::Key;
+}
+
 /// A state of a `MtcItem` used for synchronising `MtcList`s correctly
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum ItemState {
@@ -167,13 +356,52 @@ pub enum ItemState {
     Neutral,
 }
 
+/// How `MtcList::sync_with` resolves conflicts. A conflict is a new item on the client and an item on the server that
+/// have the same uuid but different contents, for example when two clients edit the same item between syncs with
+/// `MtcList::update_where`. An item edited only on the client isn't a conflict since the server still has the version
+/// the client edited.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum MergeStrategy {
+    /// Keeps both versions of the item. This is what `MtcList::sync` does.
+    #[default]
+    KeepBoth,
+    /// Keeps the client's version and removes the server's version.
+    PreferClient,
+    /// Keeps the server's version and drops the client's version.
+    PreferServer,
+}
+
+/// Criteria for finding `MtcItem`s with `MtcItem::matches` or `MtcList::query`. Criteria that are `None` match every item.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Query {
+    /// A case-insensitive part of the body of the item.
+    pub text: Option<String>,
+    /// A date the item must be for.
+    pub date: Option<NaiveDate>,
+    /// The state the item must have.
+    pub state: Option<ItemState>,
+}
+
 /// A wrapper for a `Vec` containing `MtcItem`s. The wrapper helps to manage the state of the items and sync them correctly.
 /// A `MtcList` can be either a client or a server list which affect the functionality of the list. Server lists don't track
 /// the state since multiple clients could be interacting with the same server.
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MtcList<T: MtcItem + Clone> {
     items: Vec<T>,
     is_server: bool,
+    // Not saved since a loaded list hasn't been changed yet.
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_synced: Option<DateTime<Utc>>,
+}
+
+impl<T: MtcItem + Clone + PartialEq> PartialEq for MtcList<T> {
+    /// Lists are equal if they have the same items and are both servers or clients. When they were changed or synced
+    /// doesn't matter.
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items && self.is_server == other.is_server
+    }
 }
 
 impl<T: MtcItem + Clone> MtcList<T> {
@@ -182,9 +410,37 @@ impl<T: MtcItem + Clone> MtcList<T> {
         MtcList {
             items: Vec::new(),
             is_server,
+            dirty: false,
+            last_synced: None,
+        }
+    }
+
+    /// Creates a new `MtcList` with space for at least `capacity` items without reallocating.
+    pub fn with_capacity(capacity: usize, is_server: bool) -> MtcList<T> {
+        MtcList {
+            items: Vec::with_capacity(capacity),
+            is_server,
+            dirty: false,
+            last_synced: None,
         }
     }
 
+    /// Reserves space for at least `additional` more items. Useful before adding many items at once.
+    pub fn reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+    }
+
+    /// Returns the amount of items the list can hold without reallocating. Items marked as removed take space too.
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    /// Frees the space that isn't used by the items. `sync_self` does this by itself when the removed items left
+    /// most of the space unused.
+    pub fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+    }
+
     /// Appends a new `MtcItem` to the list setting the item's state to new. Returns the id of the item.
     pub fn add(&mut self, mut item: T) -> usize {
         if self.is_server {
@@ -195,6 +451,7 @@ impl<T: MtcItem + Clone> MtcList<T> {
         item.set_id(self.items.len());
         let id = item.id();
         self.items.push(item);
+        self.dirty = true;
 
         id
     }
@@ -212,22 +469,175 @@ impl<T: MtcItem + Clone> MtcList<T> {
                 }
                 item.set_state(ItemState::Removed);
             }
+            self.dirty = true;
             Ok(())
         } else {
             Err("No item with the given id found.")
         }
     }
 
-    /// Returns a reference to the item with the id if it exists.
-    pub fn get_by_id(&self, id: usize) -> Option<&T> {
-        let item = self.items.get(id);
-        item.map_or(None, |i| {
-            if i.state() != ItemState::Removed {
-                Some(i)
+    /// Marks every `MtcItem` matching the predicate to be removed and returns the amount of marked items.
+    /// Like with `mark_removed`, server lists remove the items immediately.
+    pub fn mark_removed_where<F: Fn(&T) -> bool>(&mut self, pred: F) -> usize {
+        let mut count = 0;
+        if self.is_server {
+            let len = self.items.len();
+            self.items.retain(|item| !pred(item));
+            count = len - self.items.len();
+            self.map_indices_to_ids();
+        } else {
+            for item in self.items.iter_mut() {
+                if item.state() != ItemState::Removed && pred(item) {
+                    item.set_state(ItemState::Removed);
+                    count += 1;
+                }
+            }
+        }
+        self.dirty |= count > 0;
+        count
+    }
+
+    /// Replaces the items of the list with the given items in one step. The new items get sequential ids and the state
+    /// `New` on client lists or `Neutral` on server lists like with `add`. Client lists keep the replaced items marked as
+    /// removed so that the removals are synced too, server lists drop them.
+    pub fn replace_all(&mut self, items: Vec<T>) {
+        if self.is_server {
+            self.items.clear();
+        } else {
+            for item in self.items.iter_mut() {
+                item.set_state(ItemState::Removed);
+            }
+        }
+        self.dirty = true;
+
+        self.reserve(items.len());
+        for item in items {
+            self.add(item);
+        }
+    }
+
+    /// Applies `update` to every item matching the predicate and returns the amount of updated items. Removed items are
+    /// skipped. On client lists an updated item is marked removed and an updated copy is added as new so that the
+    /// change is synced like any other edit. This means that the ids of the updated items change. Server lists update
+    /// the items in place.
+    pub fn update_where<F, G>(&mut self, pred: F, mut update: G) -> usize
+    where
+        F: Fn(&T) -> bool,
+        G: FnMut(&mut T),
+    {
+        let matching: Vec<usize> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.state() != ItemState::Removed && pred(item))
+            .map(|(i, _)| i)
+            .collect();
+
+        for i in matching.iter() {
+            if self.is_server {
+                update(&mut self.items[*i]);
             } else {
-                None
+                let mut new = self.items[*i].clone();
+                self.items[*i].set_state(ItemState::Removed);
+                update(&mut new);
+                self.add(new);
             }
-        })
+        }
+        self.dirty |= !matching.is_empty();
+        matching.len()
+    }
+
+    /// Removes all expired `MtcItem`s and returns copies of them, for example for archiving. Client lists
+    /// only mark the items removed so that the removal is still synced, server lists remove them immediately.
+    /// See `MtcItem::expired` of the item type for which items are expired; for example todos for a passed weekday
+    /// are but todos for any day aren't.
+    pub fn drain_expired(&mut self) -> Vec<T> {
+        self.drain_where(|item| item.expired())
+    }
+
+    /// Returns references to all items that are expired without removing them. These are the items that
+    /// `remove_expired` and `drain_expired` would remove.
+    pub fn expired_items(&self) -> Vec<&T> {
+        self.items()
+            .into_iter()
+            .filter(|item| item.expired())
+            .collect()
+    }
+
+    /// Removes all `MtcItem`s matching the predicate and returns copies of them. Like with `drain_expired`, client
+    /// lists only mark the items removed while server lists remove them immediately.
+    pub fn drain_where<F: Fn(&T) -> bool>(&mut self, pred: F) -> Vec<T> {
+        let drained: Vec<T> = self
+            .items
+            .iter()
+            .filter(|item| item.state() != ItemState::Removed && pred(item))
+            .cloned()
+            .collect();
+        self.mark_removed_where(pred);
+        drained
+    }
+
+    /// Removes the `MtcItem` with the given id from the list and returns it. Unlike `mark_removed` the item is
+    /// removed right away from client lists too and the ids of the following items shift down by one. The removal
+    /// isn't synced so this is meant for moving the item somewhere else, for example with `add`. Returns `None` if
+    /// there is no such item or it is marked as removed.
+    ///
+    /// # Example
+    /// ```
+    /// use mtc::{MtcItem, MtcList, Todo};
+    ///
+    /// let mut list = MtcList::new(false);
+    /// list.add(Todo::new("Todo 1".to_string(), None));
+    /// list.add(Todo::new("Todo 2".to_string(), None));
+    ///
+    /// let todo = list.take_by_id(0).unwrap();
+    ///
+    /// assert_eq!(todo.body(), "Todo 1");
+    /// assert_eq!(list.items().len(), 1);
+    /// assert_eq!(list.items()[0].id(), 0);
+    /// ```
+    pub fn take_by_id(&mut self, id: usize) -> Option<T> {
+        // Removed items are only kept for syncing so they can't be taken.
+        self.get_by_id(id)?;
+        let item = self.items.remove(id);
+        self.map_indices_to_ids();
+        self.dirty = true;
+        Some(item)
+    }
+
+    /// Moves all `MtcItem`s matching the predicate from `source` to this list and returns the amount of moved items.
+    /// The items are removed from `source` like with `drain_where` and added to this list like with `add`.
+    pub fn append_from<F: Fn(&T) -> bool>(&mut self, source: &mut MtcList<T>, pred: F) -> usize {
+        let moved = source.drain_where(pred);
+        let count = moved.len();
+        self.reserve(count);
+        for item in moved {
+            self.add(item);
+        }
+        count
+    }
+
+    /// Returns a reference to the item with the id if it exists.
+    pub fn get_by_id(&self, id: usize) -> Option<&T> {
+        self.items
+            .get(id)
+            .filter(|i| i.state() != ItemState::Removed)
+    }
+
+    /// Returns a reference to the first non-removed item matching the predicate without collecting the rest.
+    pub fn find<F: Fn(&T) -> bool>(&self, pred: F) -> Option<&T> {
+        self.items
+            .iter()
+            .find(|item| item.state() != ItemState::Removed && pred(item))
+    }
+
+    /// Returns references to all items whose body matches the given body exactly. Useful for finding
+    /// duplicates since the sync algorithm compares items by their contents.
+    pub fn get_all_by_body(&self, body: &str) -> Vec<&T> {
+        self.items()
+            .into_iter()
+            .filter(|item| item.body() == body)
+            .collect()
     }
 
     /// Returns a new `Vec` containing references to all items within this list in the same order. Note that this filters all items that are marked as removed.
@@ -243,6 +653,38 @@ impl<T: MtcItem + Clone> MtcList<T> {
         new
     }
 
+    /// Returns a new `Vec` containing references to all items that are not marked as removed sorted with the
+    /// comparator instead of the `Ord` of the items. The sort is stable so equal items keep the order of the list.
+    pub fn items_sorted_by<F>(&self, mut cmp: F) -> Vec<&T>
+    where
+        F: FnMut(&T, &T) -> std::cmp::Ordering,
+    {
+        let mut items = self.items();
+        items.sort_by(|a, b| cmp(a, b));
+        items
+    }
+
+    /// Returns an iterator over mutable references to all items that are not marked as removed. The states of the
+    /// items are not changed so on a client list the changes are not synced and the next sync replaces the changed
+    /// items with the server's versions. Use `update_where` for edits that should be synced.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        // The items may be changed through the iterator so the list counts as changed either way.
+        self.dirty = true;
+        self.items
+            .iter_mut()
+            .filter(|item| item.state() != ItemState::Removed)
+    }
+
+    /// Returns a new `Vec` containing references to all items matching the `Query`. Items marked as
+    /// removed are only returned if the query explicitly asks for the `Removed` state.
+    pub fn query(&self, query: &Query) -> Vec<&T> {
+        self.items
+            .iter()
+            .filter(|item| query.state.is_some() || item.state() != ItemState::Removed)
+            .filter(|item| item.matches(query))
+            .collect()
+    }
+
     /// Returns a new `Vec` containing references to all items that are for a given date.
     pub fn items_for_date(&self, date: NaiveDate) -> Vec<&T> {
         self.items()
@@ -251,6 +693,13 @@ impl<T: MtcItem + Clone> MtcList<T> {
             .collect()
     }
 
+    /// Return a new `Vec` containing references to all items that are for today in the given timezone instead
+    /// of the local one. During a DST change the date only changes at midnight so the result is the same as with
+    /// `items_for_date` and the current date of the timezone.
+    pub fn items_for_today_in<Tz: TimeZone>(&self, tz: &Tz) -> Vec<&T> {
+        self.items_for_date(Utc::now().with_timezone(tz).date().naive_local())
+    }
+
     /// Return a new `Vec` containing references to all items that are for today.
     pub fn items_for_today(&self) -> Vec<&T> {
         self.items()
@@ -260,6 +709,11 @@ impl<T: MtcItem + Clone> MtcList<T> {
     }
 
     /// Return a `Vec` containing references to all items that are for a given weekday.
+    ///
+    /// The weekday is resolved to its next date (today included) and the items are matched against
+    /// that date. For items bound to a single date, such as `Event`s, this means that only items on
+    /// the next date of the weekday are returned. Use `items_for_weekday_recurring` to match items on
+    /// any date of the weekday.
     pub fn items_for_weekday(&self, weekday: Weekday) -> Vec<&T> {
         self.items()
             .into_iter()
@@ -267,6 +721,58 @@ impl<T: MtcItem + Clone> MtcList<T> {
             .collect()
     }
 
+    /// Return a `Vec` containing references to all items that are for a given weekday on any date.
+    /// Unlike `items_for_weekday`, items bound to a single date are returned if the weekday of their
+    /// date matches. Items repeating weekly behave the same with both functions.
+    pub fn items_for_weekday_recurring(&self, weekday: Weekday) -> Vec<&T> {
+        self.items()
+            .into_iter()
+            .filter(|item| item.for_weekday_recurring(weekday))
+            .collect()
+    }
+
+    /// Returns the items of every weekday like `items_for_weekday_recurring` in one call. The 0th element holds the
+    /// items for monday like in `Task::weekdays`. Items for multiple weekdays are in each of their weekdays.
+    pub fn items_grouped_by_weekday(&self) -> [Vec<&T>; 7] {
+        let mut groups: [Vec<&T>; 7] = Default::default();
+        let mut weekday = Weekday::Mon;
+        for group in groups.iter_mut() {
+            *group = self.items_for_weekday_recurring(weekday);
+            weekday = weekday.succ();
+        }
+        groups
+    }
+
+    /// Returns the amount of items for a given date without collecting them. Equal to `items_for_date(date).len()`.
+    pub fn count_for_date(&self, date: NaiveDate) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.state() != ItemState::Removed && item.for_date(date))
+            .count()
+    }
+
+    /// Returns the amount of items for a given weekday on any date without collecting them. Equal to
+    /// `items_for_weekday_recurring(weekday).len()`.
+    pub fn count_for_weekday(&self, weekday: Weekday) -> usize {
+        self.items
+            .iter()
+            .filter(|item| {
+                item.state() != ItemState::Removed && item.for_weekday_recurring(weekday)
+            })
+            .count()
+    }
+
+    /// Returns a new list with clones of the non-removed items matching the predicate. The new list is a server or
+    /// a client like this one and the items get sequential ids and the state like with `add`.
+    pub fn clone_filtered<F: Fn(&T) -> bool>(&self, pred: F) -> MtcList<T> {
+        let matching: Vec<&T> = self.items().into_iter().filter(|item| pred(item)).collect();
+        let mut list = MtcList::with_capacity(matching.len(), self.is_server);
+        for item in matching {
+            list.add(item.clone());
+        }
+        list
+    }
+
     /// Returns a clone of this list but as a server
     pub fn clone_to_server(&self) -> MtcList<T> {
         let mut clone = self.clone();
@@ -276,19 +782,130 @@ impl<T: MtcItem + Clone> MtcList<T> {
         clone
     }
 
+    /// Returns a clone of this list but as a client. This is the inverse of `clone_to_server` for
+    /// already synced lists since all items of the clone will have the `Neutral` state.
+    pub fn clone_to_client(&self) -> MtcList<T> {
+        let mut clone = self.clone();
+        clone.is_server = false;
+        clone.sync_self();
+
+        clone
+    }
+
+    /// Returns true if the list is a server list.
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// Returns true if the list may have changed since it was created, loaded or last synced. Every method taking
+    /// `&mut self` that can change the items makes the list dirty, including `iter_mut` and the `_mut` getters
+    /// whether or not the items are changed through them. Unlike the item states this isn't saved, so a loaded
+    /// client list can still have `New` and `Removed` items waiting for a sync without being dirty.
+    ///
+    /// # Example
+    /// ```
+    /// use mtc::{MtcList, Todo};
+    ///
+    /// let mut list = MtcList::new(false);
+    /// assert!(!list.is_dirty());
+    ///
+    /// list.add(Todo::new("Todo".to_string(), None));
+    /// assert!(list.is_dirty());
+    ///
+    /// list.sync_self();
+    /// assert!(!list.is_dirty());
+    /// ```
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Returns when the list was last synced, with a server or with itself, or `None` if it never has been. Unlike
+    /// `is_dirty` this is saved with the list.
+    pub fn last_synced(&self) -> Option<DateTime<Utc>> {
+        self.last_synced
+    }
+
+    /// Returns the number of items in each state as `(new, neutral, removed)`. Removed items that are still
+    /// waiting to be synced are counted too, which is helpful when finding out why a sync changed more than expected.
+    pub fn state_counts(&self) -> (usize, usize, usize) {
+        self.items
+            .iter()
+            .fold((0, 0, 0), |(new, neutral, removed), item| {
+                match item.state() {
+                    ItemState::New => (new + 1, neutral, removed),
+                    ItemState::Neutral => (new, neutral + 1, removed),
+                    ItemState::Removed => (new, neutral, removed + 1),
+                }
+            })
+    }
+
+    /// Returns references to the items that the next sync sends to the server as `(new, removed)`. Unlike `items`
+    /// this includes the items marked removed. Server lists have no pending items.
+    pub fn pending(&self) -> (Vec<&T>, Vec<&T>) {
+        let new = self
+            .items
+            .iter()
+            .filter(|item| item.state() == ItemState::New)
+            .collect();
+        let removed = self
+            .items
+            .iter()
+            .filter(|item| item.state() == ItemState::Removed)
+            .collect();
+        (new, removed)
+    }
+
+    /// Returns true if both lists have the same non-removed items compared with `ignore_state_eq`, regardless of
+    /// their order, ids and states. Duplicates must appear the same amount of times in both lists.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        let own = self.items();
+        let mut others = other.items();
+        if own.len() != others.len() {
+            return false;
+        }
+        for item in own {
+            match others.iter().position(|o| item.ignore_state_eq(o)) {
+                Some(i) => {
+                    others.swap_remove(i);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
     /// Synchronizes the list with itself by removing all items with the `Removed` state and setting the state of the rest to `Neutral`.
+    ///
+    /// Ids are indices so they can't be fully stable, but the remaining items keep their relative order. Items before
+    /// the first removed item keep their ids and the ids of the later ones only shrink by the amount of removed items
+    /// before them.
+    ///
+    /// The list is no longer dirty afterwards and its `last_synced` time is set to now.
     pub fn sync_self(&mut self) {
         self.items.retain(|item| item.state() != ItemState::Removed);
+        // Only shrinking when over half of the space is unused avoids reallocating after every small removal.
+        if self.items.len() < self.items.capacity() / 2 {
+            self.items.shrink_to_fit();
+        }
         for (i, item) in self.items.iter_mut().enumerate() {
             item.set_state(ItemState::Neutral);
             item.set_id(i);
         }
+        self.dirty = false;
+        self.last_synced = Some(Utc::now());
     }
 
     /// Synchronizes this `MtcList` with the other `MtcList`.
     /// Either one of these lists is expected to be a server and the other a client.
     /// Removes items that are marked for removal.
     ///
+    /// Both lists keep the relative order of their surviving items like with `sync_self` and items received from the
+    /// other list are added after them, so existing ids only change by the gaps left by removed items.
+    ///
+    /// Items are matched with `MtcItem::ignore_state_eq`. A neutral client item is replaced by the server's version of
+    /// it, so an edit made by marking an item removed and adding the changed copy reaches every client even if it only
+    /// changes fields that `ignore_state_eq` doesn't compare.
+    ///
     /// # Panics
     ///
     /// If neither one of the lists is a server or if both are servers.
@@ -358,6 +975,8 @@ impl<T: MtcItem + Clone> MtcList<T> {
             client_list = self;
         }
 
+        // Server items already matched to a neutral client item so that duplicates are matched one to one.
+        let mut matched = vec![false; server_list.items.len()];
         for item in client_list.items.iter_mut() {
             match item.state() {
                 ItemState::Removed => {
@@ -375,13 +994,26 @@ impl<T: MtcItem + Clone> MtcList<T> {
                     server_list.add(item.clone());
                 }
                 ItemState::Neutral => {
-                    // Check if server list contains the item. If not it should be removed.
-                    if server_list
+                    matched.resize(server_list.items.len(), false);
+                    let found = server_list.items.iter().enumerate().position(|(i, elem)| {
+                        !matched[i]
+                            && elem.state() != ItemState::Removed
+                            && elem.ignore_state_eq(item)
+                    });
+                    if let Some(i) = found {
+                        // The server's version replaces the client's so that changes to fields that items aren't
+                        // compared by, such as the order of a todo, reach every client.
+                        matched[i] = true;
+                        let id = item.id();
+                        *item = server_list.items[i].clone();
+                        item.set_id(id);
+                        item.set_state(ItemState::Neutral);
+                    } else if !server_list
                         .items
                         .iter()
-                        .position(|elem| elem.ignore_state_eq(item))
-                        .is_none()
+                        .any(|elem| elem.ignore_state_eq(item))
                     {
+                        // The server list doesn't contain the item so it should be removed.
                         item.set_state(ItemState::Removed);
                     }
                 }
@@ -412,15 +1044,57 @@ impl<T: MtcItem + Clone> MtcList<T> {
         server_list.sync_self();
     }
 
-    /// Removes all `MtcItem`s that are expired.
+    /// Removes all `MtcItem`s that are expired like `drain_expired` does.
     pub fn remove_expired(&mut self) {
         for item in self.items.iter_mut() {
             if item.expired() {
                 item.set_state(ItemState::Removed);
+                self.dirty = true;
             }
         }
     }
 
+    /// Checks that the list is internally consistent and returns a message about the first problem found. The
+    /// invariants are:
+    ///
+    /// - The id of every item is its index in the list, which also means that no two items share an id.
+    /// - A server list has only `Neutral` items since servers don't track states.
+    ///
+    /// Every method of `MtcList` keeps these so a failure means a bug or a list edited by hand. This is cheap enough
+    /// to call after every change in tests.
+    ///
+    /// # Example
+    /// ```
+    /// use mtc::{MtcList, Todo};
+    ///
+    /// let mut list = MtcList::new(false);
+    /// list.add(Todo::new("Todo".to_string(), None));
+    /// list.mark_removed(0).unwrap();
+    /// list.sync_self();
+    ///
+    /// assert_eq!(list.check_invariants(), Ok(()));
+    /// ```
+    pub fn check_invariants(&self) -> Result<(), String> {
+        for (i, item) in self.items.iter().enumerate() {
+            if item.id() != i {
+                return Err(format!(
+                    "The item '{}' at index {} has the id {}.",
+                    item.body(),
+                    i,
+                    item.id()
+                ));
+            }
+            if self.is_server && item.state() != ItemState::Neutral {
+                return Err(format!(
+                    "The item '{}' of a server list is {:?} instead of Neutral.",
+                    item.body(),
+                    item.state()
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn map_indices_to_ids(&mut self) {
         for (i, item) in self.items.iter_mut().enumerate() {
             item.set_id(i);
@@ -428,6 +1102,230 @@ impl<T: MtcItem + Clone> MtcList<T> {
     }
 }
 
+impl<T: MtcItem + Clone> Extend<T> for MtcList<T> {
+    /// Appends every item of the iterator with `add`. Unlike with a `Vec` the items don't keep their state and id,
+    /// each one is set to new on a client and neutral on a server and gets the next free id.
+    ///
+    /// # Example
+    /// ```
+    /// use mtc::{ItemState, MtcItem, MtcList, Todo};
+    ///
+    /// let mut list = MtcList::new(false);
+    /// list.extend(vec![
+    ///     Todo::new("Todo 1".to_string(), None).with_state(ItemState::Removed),
+    ///     Todo::new("Todo 2".to_string(), None),
+    /// ]);
+    ///
+    /// assert_eq!(list.items()[0].state(), ItemState::New);
+    /// assert_eq!(list.items()[1].id(), 1);
+    /// ```
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for item in iter {
+            self.add(item);
+        }
+    }
+}
+
+impl<T: MtcItem + Clone> FromIterator<T> for MtcList<T> {
+    /// Collects the items into a new client list with `add`, so every item is new and the ids follow the order of
+    /// the iterator. Use `MtcList::new(true)` and `extend` for a server list.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = MtcList::new(false);
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: MtcItem + Clone + HasUuid> MtcList<T> {
+    /// Synchronizes the list with another list like `sync` but resolves conflicting versions of an item with the
+    /// given strategy. `MergeStrategy::KeepBoth` is the same as `sync`.
+    ///
+    /// # Panics
+    /// Panics if both or neither of the lists are servers.
+    pub fn sync_with(&mut self, other: &mut MtcList<T>, strategy: MergeStrategy) {
+        if self.is_server == other.is_server {
+            // Panics with the same message as a plain sync.
+            self.sync(other);
+        }
+
+        let (server_list, client_list) = if self.is_server {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        // A version loses if the other side has a different version of the item but not this one.
+        let loses = |item: &T, others: &[&T]| {
+            let same_uuid: Vec<&&T> = others.iter().filter(|o| o.uuid() == item.uuid()).collect();
+            !same_uuid.is_empty() && !same_uuid.iter().any(|o| o.ignore_state_eq(item))
+        };
+
+        let client_new: Vec<&T> = client_list
+            .items
+            .iter()
+            .filter(|item| item.state() == ItemState::New)
+            .collect();
+        let server_items = server_list.items();
+        match strategy {
+            MergeStrategy::KeepBoth => {}
+            MergeStrategy::PreferClient => {
+                let losing: Vec<Uuid> = server_items
+                    .iter()
+                    .filter(|item| loses(item, &client_new))
+                    .map(|item| item.uuid())
+                    .collect();
+                server_list.mark_removed_where(|item| losing.contains(&item.uuid()));
+            }
+            MergeStrategy::PreferServer => {
+                // The version the client edited is kept marked removed. If the server still has that version, only the
+                // client changed the item and there is no conflict.
+                let server_changed = |item: &T| {
+                    let base: Vec<&T> = client_list
+                        .items
+                        .iter()
+                        .filter(|i| i.state() == ItemState::Removed && i.uuid() == item.uuid())
+                        .collect();
+                    !server_items.iter().any(|s| {
+                        s.uuid() == item.uuid() && base.iter().any(|b| b.ignore_state_eq(s))
+                    })
+                };
+                let losing: Vec<Uuid> = client_new
+                    .iter()
+                    .filter(|item| loses(item, &server_items) && server_changed(item))
+                    .map(|item| item.uuid())
+                    .collect();
+                // Dropped instead of marked removed so that the sync doesn't remove a similar item on the server. The
+                // edited version is dropped too so that it doesn't remove the server's version.
+                client_list.items.retain(|item| {
+                    item.state() == ItemState::Neutral || !losing.contains(&item.uuid())
+                });
+                client_list.map_indices_to_ids();
+            }
+        }
+
+        client_list.sync(server_list);
+    }
+}
+
+impl<T: MtcItem + Clone + Keyed> MtcList<T> {
+    /// Returns a reference to the item with the key if it exists. Removed items are skipped like with `get_by_id`.
+    pub fn get_by_key(&self, key: &T::Key) -> Option<&T> {
+        self.items
+            .iter()
+            .find(|i| i.key() == *key && i.state() != ItemState::Removed)
+    }
+
+    /// Returns a mutable reference to the item with the key if it exists.
+    pub fn get_by_key_mut(&mut self, key: &T::Key) -> Option<&mut T> {
+        self.dirty = true;
+        self.items
+            .iter_mut()
+            .find(|i| i.key() == *key && i.state() != ItemState::Removed)
+    }
+
+    /// Marks the item with the key to be removed. Works like `mark_removed`.
+    pub fn mark_removed_by_key(&mut self, key: &T::Key) -> Result<(), &str> {
+        match self
+            .items
+            .iter()
+            .position(|i| i.key() == *key && i.state() != ItemState::Removed)
+        {
+            Some(index) => self.mark_removed(index),
+            None => Err("No item with the given key found."),
+        }
+    }
+}
+
+impl<T: MtcItem + Clone + Keyed<Key = Uuid>> MtcList<T> {
+    /// Returns a reference to the item with the uuid if it exists. Same as `get_by_key` for items keyed by their uuid.
+    /// Unlike ids, uuids don't change when the list is synced so they can be used to refer to an item for a longer
+    /// time.
+    pub fn get_by_uuid(&self, uuid: Uuid) -> Option<&T> {
+        self.get_by_key(&uuid)
+    }
+
+    /// Returns a mutable reference to the item with the uuid if it exists. Same as `get_by_key_mut`.
+    pub fn get_by_uuid_mut(&mut self, uuid: Uuid) -> Option<&mut T> {
+        self.get_by_key_mut(&uuid)
+    }
+}
+
+impl<T: MtcItem + Clone + Ord> MtcList<T> {
+    /// Sorts the items by their `Ord` implementation and updates the ids to match the new order.
+    /// Lists with the same content in a different order will be equal after sorting which is useful
+    /// for saving them deterministically. Removed items are kept so that they can still be synced.
+    pub fn sort(&mut self) {
+        self.items.sort();
+        self.map_indices_to_ids();
+        self.dirty = true;
+    }
+}
+
+impl MtcList<Task> {
+    /// Returns the sum of the durations in minutes of the tasks for the date. The sum is a `u64` so that it can't
+    /// overflow.
+    pub fn total_minutes_for_date(&self, date: NaiveDate) -> u64 {
+        self.items_for_date(date)
+            .iter()
+            .map(|task| task.duration() as u64)
+            .sum()
+    }
+}
+
+/// The todos, tasks and events of one client or server together.
+#[derive(Debug, Clone)]
+pub struct Items {
+    /// The todos.
+    pub todos: MtcList<Todo>,
+    /// The tasks.
+    pub tasks: MtcList<Task>,
+    /// The events.
+    pub events: MtcList<Event>,
+}
+
+/// The items of every type for one date. Returned by [`Items::items_for_date`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayView<'a> {
+    /// The date the items are for.
+    pub date: NaiveDate,
+    /// The todos for the date.
+    pub todos: Vec<&'a Todo>,
+    /// The tasks for the date.
+    pub tasks: Vec<&'a Task>,
+    /// The events for the date.
+    pub events: Vec<&'a Event>,
+}
+
+impl DayView<'_> {
+    /// Returns true if there are no items of any type for the date.
+    pub fn is_empty(&self) -> bool {
+        self.todos.is_empty() && self.tasks.is_empty() && self.events.is_empty()
+    }
+}
+
+impl Items {
+    /// Creates empty lists which are all servers or all clients.
+    pub fn new(is_server: bool) -> Items {
+        Items {
+            todos: MtcList::new(is_server),
+            tasks: MtcList::new(is_server),
+            events: MtcList::new(is_server),
+        }
+    }
+
+    /// Returns the items of every type for a date like `MtcList::items_for_date` does for one type.
+    pub fn items_for_date(&self, date: NaiveDate) -> DayView<'_> {
+        DayView {
+            date,
+            todos: self.todos.items_for_date(date),
+            tasks: self.tasks.items_for_date(date),
+            events: self.events.items_for_date(date),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -685,28 +1583,457 @@ mod tests {
         expected[1].set_id(3);
         expected[2].set_id(4);
 
-        let result: Vec<Event> = items
-            .items_for_date(NaiveDate::from_ymd(2021, 10, 5))
-            .iter()
-            .cloned()
-            .cloned()
-            .collect();
+        let result: Vec<Event> = items
+            .items_for_date(NaiveDate::from_ymd(2021, 10, 5))
+            .iter()
+            .cloned()
+            .cloned()
+            .collect();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn mtc_list_for_weekday_matches_next_date_for_events() {
+        let today = Local::today().naive_local();
+        let mut items = MtcList::new(false);
+        items.add(Event::new("test0".to_string(), today));
+        items.add(Event::new(
+            "test1".to_string(),
+            today + chrono::Duration::weeks(1),
+        ));
+        items.add(Event::new("test2".to_string(), today.succ()));
+
+        let result: Vec<&String> = items
+            .items_for_weekday(today.weekday())
+            .iter()
+            .map(|item| item.body())
+            .collect();
+
+        assert_eq!(result, vec!["test0"]);
+    }
+
+    #[test]
+    fn mtc_list_for_weekday_recurring_matches_any_date_for_events() {
+        let today = Local::today().naive_local();
+        let mut items = MtcList::new(false);
+        items.add(Event::new("test0".to_string(), today));
+        items.add(Event::new(
+            "test1".to_string(),
+            today + chrono::Duration::weeks(1),
+        ));
+        items.add(Event::new("test2".to_string(), today.succ()));
+        items.add(Event::new(
+            "test3".to_string(),
+            today - chrono::Duration::weeks(3),
+        ));
+
+        let result: Vec<&String> = items
+            .items_for_weekday_recurring(today.weekday())
+            .iter()
+            .map(|item| item.body())
+            .collect();
+
+        assert_eq!(result, vec!["test0", "test1", "test3"]);
+    }
+
+    #[test]
+    fn mtc_list_for_weekday_recurring_equals_for_weekday_for_tasks() {
+        let mut items = MtcList::new(true);
+        items.add(Task::new("test0".to_string(), 40, Some(Weekday::Fri)));
+        items.add(Task::new("test1".to_string(), 10, Some(Weekday::Mon)));
+        items.add(Task::new("test2".to_string(), 0, None));
+
+        for wd in [Weekday::Mon, Weekday::Fri, Weekday::Sun] {
+            assert_eq!(
+                items.items_for_weekday(wd),
+                items.items_for_weekday_recurring(wd)
+            );
+        }
+    }
+
+    #[test]
+    fn mtc_list_items_grouped_by_weekday_returns_expected() {
+        let mut items = MtcList::new(true);
+        items.add(
+            TaskBuilder::new("test0".to_string(), 40)
+                .weekday(Weekday::Mon)
+                .weekday(Weekday::Fri)
+                .build(),
+        );
+        items.add(Task::new("test1".to_string(), 10, Some(Weekday::Tue)));
+        items.add(Task::new("test2".to_string(), 10, None));
+
+        let groups = items.items_grouped_by_weekday();
+        let ids: Vec<Vec<usize>> = groups
+            .iter()
+            .map(|group| group.iter().map(|task| task.id()).collect())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                vec![0, 2],
+                vec![1, 2],
+                vec![2],
+                vec![2],
+                vec![0, 2],
+                vec![2],
+                vec![2]
+            ]
+        );
+    }
+
+    #[test]
+    fn mtc_list_query_returns_expected() {
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Run".to_string(), 30, Some(Weekday::Mon)));
+        list.add(Task::new("Read".to_string(), 30, Some(Weekday::Tue)));
+        list.add(Task::new("Run far".to_string(), 90, Some(Weekday::Tue)));
+        list.add(Task::new("Rest".to_string(), 10, None));
+        list.sync_self();
+        list.mark_removed(3).unwrap();
+
+        let ids = |query: &Query| -> Vec<usize> {
+            list.query(query).iter().map(|item| item.id()).collect()
+        };
+
+        assert_eq!(ids(&Query::default()), vec![0, 1, 2]);
+
+        let query = Query {
+            text: Some("RUN".to_string()),
+            ..Query::default()
+        };
+        assert_eq!(ids(&query), vec![0, 2]);
+
+        // 2021.11.30 was a tuesday.
+        let query = Query {
+            text: Some("run".to_string()),
+            date: Some(NaiveDate::from_ymd(2021, 11, 30)),
+            ..Query::default()
+        };
+        assert_eq!(ids(&query), vec![2]);
+
+        let query = Query {
+            state: Some(ItemState::Removed),
+            ..Query::default()
+        };
+        assert_eq!(ids(&query), vec![3]);
+    }
+
+    #[test]
+    fn mtc_list_get_by_id_returns_some_and_none() {
+        let todo = Todo::new("Item".to_string(), None);
+        let mut list = MtcList::new(false);
+        let id = list.add(todo.clone());
+
+        assert!(todo.ignore_state_eq(list.get_by_id(id).unwrap()));
+
+        list.mark_removed(id).unwrap();
+
+        assert_eq!(None, list.get_by_id(id));
+        assert_eq!(None, list.get_by_id(66));
+    }
+
+    #[test]
+    fn mtc_list_mark_removed_where_marks_matching_on_client() {
+        let mut list = MtcList::new(false);
+        list.add(Todo::new("Item 0".to_string(), Some(Weekday::Mon)));
+        list.add(Todo::new("Item 1".to_string(), Some(Weekday::Tue)));
+        list.add(Todo::new("Item 2".to_string(), Some(Weekday::Mon)));
+
+        list.mark_removed(2).unwrap();
+
+        assert_eq!(
+            list.mark_removed_where(|item| item.for_weekday(Weekday::Mon)),
+            1
+        );
+
+        let bodies: Vec<&String> = list.items().iter().map(|item| item.body()).collect();
+        assert_eq!(bodies, vec!["Item 1"]);
+        assert_eq!(list.get_by_id(1).unwrap().id(), 1);
+    }
+
+    #[test]
+    fn mtc_list_mark_removed_where_removes_matching_on_server() {
+        let mut list = MtcList::new(true);
+        list.add(Todo::new("Item 0".to_string(), Some(Weekday::Mon)));
+        list.add(Todo::new("Item 1".to_string(), Some(Weekday::Tue)));
+        list.add(Todo::new("Item 2".to_string(), Some(Weekday::Mon)));
+        list.add(Todo::new("Item 3".to_string(), Some(Weekday::Wed)));
+
+        assert_eq!(
+            list.mark_removed_where(|item| item.for_weekday(Weekday::Mon)),
+            2
+        );
+
+        let mut expected = MtcList::new(true);
+        expected.add(Todo::new("Item 1".to_string(), Some(Weekday::Tue)));
+        expected.add(Todo::new("Item 3".to_string(), Some(Weekday::Wed)));
+
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn summary_line_cuts_multibyte_body_at_char_boundary() {
+        let mut todo = Todo::new("Käy kävelyllä 🚶 puistossa".to_string(), None);
+        todo.set_id(12);
+
+        let line = todo.summary_line(21);
+        assert_eq!(line, "12   Käy kävelyllä 🚶…");
+        assert_eq!(line.chars().count(), 21);
+        assert_eq!(todo.summary_line(1), "…");
+        assert_eq!(todo.summary_line(0), "");
+    }
+
+    #[test]
+    fn summary_lines_are_aligned() {
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Read".to_string(), 15, None));
+        for i in 0..10 {
+            list.add(Task::new(format!("Exercise {}", i), 30, None));
+        }
+
+        let lines: Vec<String> = list.items().iter().map(|t| t.summary_line(16)).collect();
+        assert_eq!(lines[0], "0    Read       ");
+        assert_eq!(lines[10], "10   Exercise 9 ");
+        assert!(lines.iter().all(|line| line.chars().count() == 16));
+    }
+
+    #[test]
+    fn mtc_list_take_by_id_removes_item_from_client_list() {
+        let mut list = MtcList::new(false);
+        list.add(Todo::new("Todo 0".to_string(), None));
+        list.add(Todo::new("Todo 1".to_string(), None));
+        list.add(Todo::new("Todo 2".to_string(), None));
+        list.mark_removed(2).unwrap();
+
+        assert!(list.take_by_id(2).is_none());
+        assert!(list.take_by_id(3).is_none());
+        let todo = list.take_by_id(0).unwrap();
+        assert_eq!(todo.body(), "Todo 0");
+
+        let mut expected = MtcList::new(false);
+        expected.add(Todo::new("Todo 1".to_string(), None));
+        expected.add(Todo::new("Todo 2".to_string(), None));
+        expected.mark_removed(1).unwrap();
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn check_invariants_finds_wrong_ids_and_server_states() {
+        let mut list = MtcList::new(false);
+        list.add(Todo::new("Todo 0".to_string(), None));
+        list.add(Todo::new("Todo 1".to_string(), None));
+        list.mark_removed(0).unwrap();
+        assert_eq!(list.check_invariants(), Ok(()));
+
+        list.items[1].set_id(0);
+        assert_eq!(
+            list.check_invariants(),
+            Err("The item 'Todo 1' at index 1 has the id 0.".to_string())
+        );
+
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Todo 1".to_string(), None));
+        let mut server = MtcList::new(true);
+        server.add(Todo::new("Todo 0".to_string(), None));
+        server.sync(&mut client);
+        assert_eq!(server.check_invariants(), Ok(()));
+        assert_eq!(client.check_invariants(), Ok(()));
+        server.items[0].set_state(ItemState::New);
+        assert_eq!(
+            server.check_invariants(),
+            Err("The item 'Todo 0' of a server list is New instead of Neutral.".to_string())
+        );
+    }
+
+    #[test]
+    fn mtc_list_tracks_dirty_and_last_synced() {
+        let mut list = MtcList::new(false);
+        list.add(Todo::new("Todo 0".to_string(), None));
+        assert!(list.is_dirty());
+        assert!(list.last_synced().is_none());
+
+        list.sync_self();
+        assert!(!list.is_dirty());
+        let synced = list.last_synced().unwrap();
+
+        assert!(list.mark_removed(5).is_err());
+        assert_eq!(list.mark_removed_where(|t| t.body() == "Todo 5"), 0);
+        assert!(!list.is_dirty());
+        list.mark_removed(0).unwrap();
+        assert!(list.is_dirty());
+
+        // Only the time of the last sync is saved.
+        let loaded: MtcList<Todo> =
+            serde_json::from_str(&serde_json::to_string(&list).unwrap()).unwrap();
+        assert!(!loaded.is_dirty());
+        assert_eq!(loaded.last_synced(), Some(synced));
+        assert_eq!(loaded, list);
+    }
+
+    #[test]
+    fn mtc_list_find_skips_removed_items() {
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Exercise".to_string(), 30, None));
+        list.add(Task::new("Exercise".to_string(), 60, None));
+        list.add(Task::new("Read".to_string(), 15, None));
+
+        assert_eq!(
+            list.find(|t| t.body() == "Exercise").unwrap().duration(),
+            30
+        );
+        list.mark_removed(0).unwrap();
+        assert_eq!(
+            list.find(|t| t.body() == "Exercise").unwrap().duration(),
+            60
+        );
+        assert!(list.find(|t| t.body() == "Sleep").is_none());
+    }
+
+    #[test]
+    fn mtc_list_items_sorted_by_skips_removed_items() {
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Exercise".to_string(), 30, None));
+        list.add(Task::new("Read".to_string(), 15, None));
+        list.add(Task::new("Cook".to_string(), 60, None));
+        list.add(Task::new("Sleep".to_string(), 5, None));
+        list.mark_removed(3).unwrap();
+
+        let bodies: Vec<&str> = list
+            .items_sorted_by(|a, b| a.duration().cmp(&b.duration()))
+            .iter()
+            .map(|t| t.body().as_str())
+            .collect();
+        assert_eq!(bodies, vec!["Read", "Exercise", "Cook"]);
+
+        let bodies: Vec<&str> = list
+            .items_sorted_by(|a, b| b.body().cmp(a.body()))
+            .iter()
+            .map(|t| t.body().as_str())
+            .collect();
+        assert_eq!(bodies, vec!["Read", "Exercise", "Cook"]);
+    }
+
+    #[test]
+    fn items_for_date_returns_every_type() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let mut items = Items::new(false);
+        items.todos.add(Todo::new_dated("Todo".to_string(), date));
+        items
+            .todos
+            .add(Todo::new_dated("Later".to_string(), date.succ()));
+        items
+            .tasks
+            .add(Task::new("Task".to_string(), 30, Some(Weekday::Mon)));
+        items.events.add(Event::new("Event".to_string(), date));
+
+        let day = items.items_for_date(date);
+        assert_eq!(day.date, date);
+        assert_eq!(day.todos.len(), 1);
+        assert_eq!(day.tasks.len(), 1);
+        assert_eq!(day.events.len(), 1);
+        assert!(!day.is_empty());
+        assert!(items.items_for_date(date.pred()).is_empty());
+    }
+
+    #[test]
+    fn mtc_list_counts_equal_item_lengths() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let mut list = MtcList::new(false);
+        list.add(Event::new("Event 0".to_string(), date));
+        list.add(Event::new("Event 1".to_string(), date));
+        list.add(Event::new("Event 2".to_string(), date.succ()));
+        list.add(Event::new(
+            "Event 3".to_string(),
+            date + chrono::Duration::days(7),
+        ));
+        list.mark_removed(1).unwrap();
+
+        assert_eq!(list.count_for_date(date), list.items_for_date(date).len());
+        assert_eq!(list.count_for_date(date), 1);
+        assert_eq!(
+            list.count_for_weekday(Weekday::Mon),
+            list.items_for_weekday_recurring(Weekday::Mon).len()
+        );
+        assert_eq!(list.count_for_weekday(Weekday::Mon), 2);
+        assert_eq!(list.count_for_weekday(Weekday::Tue), 1);
+    }
+
+    #[test]
+    fn mtc_list_content_eq_ignores_order_and_state() {
+        let mut list1 = MtcList::new(false);
+        list1.add(Todo::new("Item 2".to_string(), None));
+        list1.add(Todo::new("Item 0".to_string(), None));
+        list1.add(Todo::new("Item 1".to_string(), None));
+        list1.add(Todo::new("Removed".to_string(), None));
+        list1.mark_removed(3).unwrap();
+
+        let mut list2 = MtcList::new(true);
+        list2.add(Todo::new("Item 1".to_string(), None));
+        list2.add(Todo::new("Item 2".to_string(), None));
+        list2.add(Todo::new("Item 0".to_string(), None));
+
+        assert!(list1.content_eq(&list2));
+        assert!(list2.content_eq(&list1));
+    }
 
-        assert_eq!(result, expected);
+    #[test]
+    fn mtc_list_content_eq_counts_duplicates() {
+        let mut list1 = MtcList::new(false);
+        list1.add(Todo::new("Item".to_string(), None));
+        list1.add(Todo::new("Item".to_string(), None));
+        list1.add(Todo::new("Other".to_string(), None));
+
+        let mut list2 = MtcList::new(false);
+        list2.add(Todo::new("Item".to_string(), None));
+        list2.add(Todo::new("Other".to_string(), None));
+        list2.add(Todo::new("Other".to_string(), None));
+
+        assert!(!list1.content_eq(&list2));
+        list2.mark_removed(2).unwrap();
+        assert!(!list1.content_eq(&list2));
     }
 
     #[test]
-    fn mtc_list_get_by_id_returns_some_and_none() {
-        let todo = Todo::new("Item".to_string(), None);
+    fn mtc_list_sort_makes_lists_equal() {
+        let mut list1 = MtcList::new(false);
+        list1.add(Todo::new("Item 2".to_string(), None));
+        list1.add(Todo::new("Item 0".to_string(), None));
+        list1.add(Todo::new("Item 1".to_string(), None));
+
+        let mut list2 = MtcList::new(false);
+        list2.add(Todo::new("Item 1".to_string(), None));
+        list2.add(Todo::new("Item 2".to_string(), None));
+        list2.add(Todo::new("Item 0".to_string(), None));
+
+        list1.sort();
+        list2.sort();
+
+        assert_eq!(list1, list2);
+        assert_eq!(list1.get_by_id(0).unwrap().body(), "Item 0");
+        assert_eq!(list1.get_by_id(2).unwrap().id(), 2);
+    }
+
+    #[test]
+    fn mtc_list_get_all_by_body_returns_expected() {
         let mut list = MtcList::new(false);
-        let id = list.add(todo.clone());
+        list.add(Todo::new("Item".to_string(), None));
+        list.add(Todo::new("Item".to_string(), Some(Weekday::Mon)));
+        list.add(Todo::new("Other".to_string(), None));
+        list.add(Todo::new("Item".to_string(), Some(Weekday::Tue)));
+        list.add(Todo::new("Item 2".to_string(), None));
 
-        assert!(todo.ignore_state_eq(list.get_by_id(id).unwrap()));
+        list.mark_removed(3).unwrap();
 
-        list.mark_removed(id).unwrap();
+        let ids: Vec<usize> = list
+            .get_all_by_body("Item")
+            .iter()
+            .map(|item| item.id())
+            .collect();
 
-        assert_eq!(None, list.get_by_id(id));
-        assert_eq!(None, list.get_by_id(66));
+        assert_eq!(ids, vec![0, 1]);
+        assert!(list.get_all_by_body("Missing").is_empty());
     }
 
     #[derive(Debug, PartialEq, Clone)]
@@ -723,7 +2050,7 @@ mod tests {
 
     impl PartialOrd for TestMtcItem {
         fn partial_cmp(&self, other: &Self) -> std::option::Option<std::cmp::Ordering> {
-            Some(self.body.cmp(&other.body))
+            Some(self.cmp(other))
         }
     }
 
@@ -751,6 +2078,9 @@ mod tests {
         fn ignore_state_eq(&self, other: &Self) -> bool {
             self.body == other.body
         }
+        fn body(&self) -> &str {
+            &self.body
+        }
         fn id(&self) -> usize {
             0
         }
@@ -813,6 +2143,105 @@ mod tests {
         assert_eq!(sorted, exp);
     }
 
+    #[test]
+    fn mtc_list_self_sync_only_closes_gaps_in_ids() {
+        let mut list = MtcList::new(false);
+
+        list.add(TestMtcItem::new("Item 0".to_string()));
+        list.add(TestMtcItem::new("Item 1".to_string()));
+        list.add(TestMtcItem::new("Item 2".to_string()));
+        list.add(TestMtcItem::new("Item 3".to_string()));
+
+        list.mark_removed(1).unwrap();
+        list.sync_self();
+
+        assert_eq!(list.get_by_id(0).unwrap().body(), "Item 0");
+        assert_eq!(list.get_by_id(1).unwrap().body(), "Item 2");
+        assert_eq!(list.get_by_id(2).unwrap().body(), "Item 3");
+    }
+
+    #[test]
+    fn mtc_list_sync_keeps_client_order_and_appends_server_items() {
+        let mut client_list = MtcList::new(false);
+        let mut server_list = MtcList::new(true);
+
+        client_list.add(TestMtcItem::new("Item 2".to_string()));
+        client_list.add(TestMtcItem::new("Item 0".to_string()));
+        client_list.add(TestMtcItem::new("Item 1".to_string()));
+        client_list.sync(&mut server_list);
+
+        server_list.add(TestMtcItem::new("Item 3".to_string()));
+        client_list.mark_removed(1).unwrap();
+        client_list.sync(&mut server_list);
+
+        let bodies: Vec<&str> = client_list.items().iter().map(|i| i.body()).collect();
+        assert_eq!(bodies, vec!["Item 2", "Item 1", "Item 3"]);
+        assert_eq!(client_list.get_by_id(1).unwrap().body(), "Item 1");
+    }
+
+    #[test]
+    fn mtc_list_sync_carries_order_changes_to_other_clients() {
+        let mut server = MtcList::new(true);
+        let mut client1 = MtcList::new(false);
+        let mut client2 = MtcList::new(false);
+
+        client1.add(Todo::new("Todo 0".to_string(), None));
+        client1.add(Todo::new("Todo 1".to_string(), None));
+        client1.sync(&mut server);
+        client2.sync(&mut server);
+
+        // Moving a todo up like the CLI does.
+        let mut moved = client1.get_by_id(1).unwrap().clone();
+        moved.set_order(-1);
+        client1.mark_removed(1).unwrap();
+        client1.add(moved);
+        client1.sync(&mut server);
+        client2.sync(&mut server);
+
+        for list in [&client1, &server, &client2] {
+            let orders: Vec<i32> = list.items().iter().map(|t| t.order()).collect();
+            assert_eq!(orders, vec![0, -1]);
+        }
+        assert_eq!(client2.get_by_id(1).unwrap().body(), "Todo 1");
+    }
+
+    #[test]
+    fn mtc_list_sync_carries_done_dates_to_other_clients() {
+        let date = NaiveDate::from_ymd(2021, 12, 6);
+        let mut server = MtcList::new(true);
+        let mut client1 = MtcList::new(false);
+        let mut client2 = MtcList::new(false);
+
+        client1.add(Task::new("Exercise".to_string(), 30, None));
+        client1.sync(&mut server);
+        client2.sync(&mut server);
+
+        // Checking the task off like 'mtc done' does.
+        let mut done = client1.get_by_id(0).unwrap().clone();
+        done.mark_done(date);
+        client1.mark_removed(0).unwrap();
+        client1.add(done);
+        client1.sync(&mut server);
+        client2.sync(&mut server);
+
+        assert!(client2.get_by_id(0).unwrap().is_done_on(date));
+    }
+
+    #[test]
+    fn mtc_list_sync_matches_duplicates_one_to_one() {
+        let mut server = MtcList::new(true);
+        let mut client = MtcList::new(false);
+
+        client.add(TodoBuilder::new("Todo".to_string()).order(1).build());
+        client.add(TodoBuilder::new("Todo".to_string()).order(2).build());
+        client.sync(&mut server);
+        server.update_where(|todo| todo.order() == 2, |todo| todo.set_order(3));
+        client.sync(&mut server);
+
+        let orders: Vec<i32> = client.items().iter().map(|t| t.order()).collect();
+        assert_eq!(orders, vec![1, 3]);
+    }
+
     #[test]
     fn mtc_list_sync_removes_marked_from_server() {
         let mut client_list = MtcList::new(false);
@@ -1092,6 +2521,22 @@ mod tests {
         assert_eq!(client.clone_to_server(), expected);
     }
 
+    #[test]
+    fn mtc_list_clone_to_client_is_inverse_of_clone_to_server() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Item 0".to_string(), None));
+        client.add(Todo::new("Item 1".to_string(), None));
+        client.sync_self();
+
+        let server = client.clone_to_server();
+        assert!(server.is_server());
+        assert!(!client.is_server());
+
+        let clone = server.clone_to_client();
+        assert!(!clone.is_server());
+        assert_eq!(clone, client);
+    }
+
     #[test]
     fn mtc_list_doesnt_return_marked_as_removed() {
         let mut client = MtcList::new(false);
@@ -1107,6 +2552,498 @@ mod tests {
         }
     }
 
+    #[test]
+    fn mtc_list_with_capacity_doesnt_reallocate() {
+        let mut list = MtcList::with_capacity(100_000, false);
+        let capacity = list.items.capacity();
+        let ptr = list.items.as_ptr();
+
+        for i in 0..100_000 {
+            list.add(Todo::new(i.to_string(), None));
+        }
+
+        assert_eq!(list.items.capacity(), capacity);
+        assert_eq!(list.items.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn mtc_list_sync_self_frees_space_after_removals() {
+        let mut list = MtcList::new(false);
+        for i in 0..1000 {
+            list.add(Todo::new(i.to_string(), None));
+        }
+        let capacity = list.capacity();
+        list.mark_removed_where(|todo| todo.body() != "0");
+
+        list.sync_self();
+
+        assert!(list.capacity() < capacity);
+        assert_eq!(list.items().len(), 1);
+    }
+
+    #[test]
+    fn mtc_list_shrink_to_fit_frees_space() {
+        let mut list = MtcList::with_capacity(1000, true);
+        list.add(Task::new("Task".to_string(), 10, None));
+        assert!(list.capacity() >= 1000);
+
+        list.shrink_to_fit();
+
+        assert!(list.capacity() < 1000);
+        assert!(list.capacity() >= 1);
+    }
+
+    #[test]
+    fn mtc_list_extend_adds_items() {
+        let mut list = MtcList::new(true);
+        list.add(Task::new("Task 0".to_string(), 10, None));
+        list.extend(vec![
+            Task::new("Task 1".to_string(), 10, None).with_state(ItemState::New),
+            Task::new("Task 2".to_string(), 10, None).with_state(ItemState::Removed),
+        ]);
+
+        let mut expected = MtcList::new(true);
+        expected.add(Task::new("Task 0".to_string(), 10, None));
+        expected.add(Task::new("Task 1".to_string(), 10, None));
+        expected.add(Task::new("Task 2".to_string(), 10, None));
+
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn mtc_list_collects_to_client_list() {
+        let list: MtcList<Todo> = ["Todo 0", "Todo 1"]
+            .iter()
+            .map(|body| Todo::new(body.to_string(), None))
+            .collect();
+
+        assert!(!list.is_server());
+        assert_eq!(list.items()[1].id(), 1);
+        assert_eq!(list.items()[1].state(), ItemState::New);
+    }
+
+    #[test]
+    fn mtc_list_reserve_doesnt_reallocate() {
+        let mut list = MtcList::new(true);
+        list.add(Task::new("Task".to_string(), 10, None));
+        list.reserve(1000);
+        let capacity = list.items.capacity();
+
+        for i in 0..1000 {
+            list.add(Task::new(i.to_string(), 10, None));
+        }
+
+        assert!(capacity >= 1001);
+        assert_eq!(list.items.capacity(), capacity);
+    }
+
+    #[test]
+    fn mtc_list_state_counts_works() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Todo 1".to_string(), None));
+        client.add(Todo::new("Todo 2".to_string(), None));
+        client.sync_self();
+        client.add(Todo::new("Todo 3".to_string(), None));
+        client.add(Todo::new("Todo 4".to_string(), None));
+        client.add(Todo::new("Todo 5".to_string(), None));
+        client.mark_removed(0).unwrap();
+
+        assert_eq!(client.state_counts(), (3, 1, 1));
+    }
+
+    #[test]
+    fn mtc_drain_expired_returns_expired() {
+        let today = Local::today().naive_local();
+        let mut client = MtcList::new(false);
+        client.add(Event::new("Event 1".to_string(), today));
+        client.add(Event::new(
+            "Event 2".to_string(),
+            today - chrono::Duration::days(5),
+        ));
+        client.add(Event::new(
+            "Event 3".to_string(),
+            today - chrono::Duration::days(6),
+        ));
+        client.mark_removed(2).unwrap();
+
+        let drained = client.drain_expired();
+        let bodies: Vec<&String> = drained.iter().map(|e| e.body()).collect();
+        assert_eq!(bodies, vec!["Event 2"]);
+        assert_eq!(client.state_counts(), (1, 0, 2));
+
+        let mut server = MtcList::new(true);
+        server.add(Event::new(
+            "Event 1".to_string(),
+            today - chrono::Duration::days(5),
+        ));
+        assert_eq!(server.drain_expired().len(), 1);
+        assert!(server.items().is_empty());
+    }
+
+    #[test]
+    fn mtc_get_by_uuid_survives_sync_self() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Todo 1".to_string(), None));
+        client.add(Todo::new("Todo 2".to_string(), None));
+        let uuid = client.get_by_id(1).unwrap().uuid();
+
+        client.mark_removed(0).unwrap();
+        client.sync_self();
+
+        let todo = client.get_by_uuid(uuid).unwrap();
+        assert_eq!(todo.body(), "Todo 2");
+        assert_eq!(todo.id(), 0);
+    }
+
+    #[test]
+    fn mtc_get_by_uuid_skips_removed() {
+        let mut client = MtcList::new(false);
+        client.add(Task::new("Task 1".to_string(), 10, None));
+        let uuid = client.get_by_id(0).unwrap().uuid();
+
+        client.get_by_uuid_mut(uuid).unwrap().set_order(2);
+        assert_eq!(client.get_by_uuid(uuid).unwrap().order(), 2);
+
+        client.mark_removed(0).unwrap();
+        assert!(client.get_by_uuid(uuid).is_none());
+        assert!(client.get_by_uuid_mut(uuid).is_none());
+    }
+
+    #[test]
+    fn mtc_uuid_survives_sync_with_server() {
+        let mut client = MtcList::new(false);
+        let mut server = MtcList::new(true);
+        client.add(Event::new(
+            "Event 1".to_string(),
+            NaiveDate::from_ymd(2022, 1, 1),
+        ));
+        let uuid = client.get_by_id(0).unwrap().uuid();
+
+        client.sync(&mut server);
+
+        assert!(client.get_by_uuid(uuid).is_some());
+        assert!(server.get_by_uuid(uuid).is_some());
+    }
+
+    #[test]
+    fn mtc_get_by_key_works() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Todo 1".to_string(), None));
+        client.add(Todo::new("Todo 2".to_string(), None));
+        let key = client.get_by_id(1).unwrap().key();
+
+        client.mark_removed(0).unwrap();
+        client.sync_self();
+        assert_eq!(client.get_by_key(&key).unwrap().body(), "Todo 2");
+
+        client.get_by_key_mut(&key).unwrap().set_order(1);
+        assert_eq!(client.get_by_id(0).unwrap().order(), 1);
+
+        client.mark_removed_by_key(&key).unwrap();
+        assert!(client.get_by_key(&key).is_none());
+        assert!(client.mark_removed_by_key(&key).is_err());
+    }
+
+    #[test]
+    fn mtc_items_for_today_in_uses_timezone() {
+        // The dates in UTC-12 and UTC+14 are always different.
+        let behind = FixedOffset::west(12 * 3600);
+        let ahead = FixedOffset::east(14 * 3600);
+        let behind_date = Utc::now().with_timezone(&behind).date().naive_local();
+
+        let mut list = MtcList::new(false);
+        list.add(Event::new("Event".to_string(), behind_date));
+
+        assert_eq!(list.items_for_today_in(&behind).len(), 1);
+        assert!(list.items_for_today_in(&ahead).is_empty());
+    }
+
+    #[test]
+    fn mtc_update_where_client_readds_items() {
+        let mut client = MtcList::new(false);
+        client.add(Task::new("Task 1".to_string(), 10, Some(Weekday::Mon)));
+        client.add(Task::new("Task 2".to_string(), 10, Some(Weekday::Tue)));
+        client.add(Task::new("Task 3".to_string(), 10, Some(Weekday::Mon)));
+        client.mark_removed(2).unwrap();
+        client.sync_self();
+        client.add(Task::new("Task 4".to_string(), 10, Some(Weekday::Mon)));
+
+        let count = client.update_where(
+            |task| task.for_weekday(Weekday::Mon),
+            |task| task.set_order(-1),
+        );
+
+        assert_eq!(count, 2);
+        assert_eq!(client.state_counts(), (2, 1, 2));
+        let updated: Vec<&String> = client
+            .items()
+            .into_iter()
+            .filter(|task| task.order() == -1)
+            .map(|task| task.body())
+            .collect();
+        assert_eq!(updated, vec!["Task 1", "Task 4"]);
+    }
+
+    #[test]
+    fn mtc_update_where_server_updates_in_place() {
+        let mut server = MtcList::new(true);
+        server.add(Todo::new("Todo 1".to_string(), None));
+        server.add(Todo::new("Todo 2".to_string(), None));
+
+        let count = server.update_where(|todo| todo.body() == "Todo 2", |todo| todo.set_order(3));
+
+        assert_eq!(count, 1);
+        assert_eq!(server.get_by_id(1).unwrap().order(), 3);
+        assert_eq!(server.items().len(), 2);
+    }
+
+    /// Returns a synced client and server where both have moved the same event to a different date since.
+    fn conflicting_lists() -> (MtcList<Event>, MtcList<Event>) {
+        let mut server = MtcList::new(true);
+        server.add(Event::new("Other".to_string(), date(1)));
+        server.add(Event::new("Meeting".to_string(), date(2)));
+        let mut client = server.clone_to_client();
+
+        client.update_where(
+            |event| event.body() == "Meeting",
+            |event| event.set_date(date(3)),
+        );
+        server.update_where(
+            |event| event.body() == "Meeting",
+            |event| event.set_date(date(4)),
+        );
+        (client, server)
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2100, 1, day)
+    }
+
+    fn days(list: &MtcList<Event>) -> Vec<u32> {
+        let mut days: Vec<u32> = list.items().iter().map(|e| e.date().day()).collect();
+        days.sort();
+        days
+    }
+
+    #[test]
+    fn mtc_sync_with_keep_both_equals_sync() {
+        let (mut client, mut server) = conflicting_lists();
+        let (mut sync_client, mut sync_server) = conflicting_lists();
+
+        client.sync_with(&mut server, MergeStrategy::KeepBoth);
+        sync_client.sync(&mut sync_server);
+
+        assert_eq!(days(&client), vec![1, 3, 4]);
+        assert_eq!(days(&client), days(&sync_client));
+        assert_eq!(days(&server), days(&sync_server));
+    }
+
+    #[test]
+    fn mtc_sync_with_prefer_client_keeps_client_version() {
+        let (mut client, mut server) = conflicting_lists();
+
+        client.sync_with(&mut server, MergeStrategy::PreferClient);
+
+        assert_eq!(days(&client), vec![1, 3]);
+        assert_eq!(days(&server), vec![1, 3]);
+    }
+
+    #[test]
+    fn mtc_sync_with_prefer_server_keeps_server_version() {
+        let (mut client, mut server) = conflicting_lists();
+
+        // Works with either list as self.
+        server.sync_with(&mut client, MergeStrategy::PreferServer);
+
+        assert_eq!(days(&client), vec![1, 4]);
+        assert_eq!(days(&server), vec![1, 4]);
+    }
+
+    #[test]
+    fn mtc_sync_with_prefer_server_keeps_client_edit_if_server_unchanged() {
+        let mut server = MtcList::new(true);
+        server.add(Event::new("Other".to_string(), date(1)));
+        server.add(Event::new("Meeting".to_string(), date(2)));
+        let mut client = server.clone_to_client();
+        client.update_where(
+            |event| event.body() == "Meeting",
+            |event| event.set_date(date(3)),
+        );
+
+        client.sync_with(&mut server, MergeStrategy::PreferServer);
+
+        assert_eq!(days(&client), vec![1, 3]);
+        assert_eq!(days(&server), vec![1, 3]);
+    }
+
+    #[test]
+    fn mtc_pending_returns_new_and_removed() {
+        let mut list = MtcList::new(false);
+        list.add(Todo::new("Synced".to_string(), None));
+        list.add(Todo::new("Removed".to_string(), None));
+        list.sync_self();
+        list.add(Todo::new("New".to_string(), None));
+        list.mark_removed(1).unwrap();
+
+        let (new, removed) = list.pending();
+        assert_eq!(
+            new.iter().map(|t| t.body()).collect::<Vec<_>>(),
+            vec!["New"]
+        );
+        assert_eq!(
+            removed.iter().map(|t| t.body()).collect::<Vec<_>>(),
+            vec!["Removed"]
+        );
+    }
+
+    #[test]
+    fn mtc_expired_items_does_not_remove() {
+        let today = Local::today().naive_local();
+        let mut list = MtcList::new(false);
+        list.add(Event::new(
+            "Old".to_string(),
+            today - chrono::Duration::days(5),
+        ));
+        list.add(Event::new("Today".to_string(), today));
+
+        let expired: Vec<&String> = list.expired_items().iter().map(|e| e.body()).collect();
+        assert_eq!(expired, vec!["Old"]);
+        assert_eq!(list.items().len(), 2);
+    }
+
+    #[test]
+    fn mtc_total_minutes_for_date_sums_durations() {
+        // A monday.
+        let date = NaiveDate::from_ymd(2022, 1, 3);
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Run".to_string(), 30, Some(Weekday::Mon)));
+        list.add(Task::new("Read".to_string(), 45, Some(Weekday::Mon)));
+        list.add(Task::new("Stretch".to_string(), 10, None));
+        list.add(Task::new("Swim".to_string(), 60, Some(Weekday::Tue)));
+        list.add(Task::new("Huge".to_string(), u32::MAX, Some(Weekday::Wed)));
+        list.add(Task::new(
+            "Huge 2".to_string(),
+            u32::MAX,
+            Some(Weekday::Wed),
+        ));
+        list.sync_self();
+        list.mark_removed(1).unwrap();
+
+        assert_eq!(list.total_minutes_for_date(date), 40);
+        assert_eq!(list.total_minutes_for_date(date.succ()), 70);
+        assert_eq!(
+            list.total_minutes_for_date(date.succ().succ()),
+            2 * u32::MAX as u64 + 10
+        );
+
+        let mut empty = MtcList::new(false);
+        empty.add(Task::new("Run".to_string(), 30, Some(Weekday::Mon)));
+        assert_eq!(empty.total_minutes_for_date(date.succ()), 0);
+    }
+
+    #[test]
+    fn mtc_iter_mut_skips_removed_and_keeps_states() {
+        let mut list = MtcList::new(false);
+        list.add(Task::new("Task 1".to_string(), 10, None));
+        list.add(Task::new("Task 2".to_string(), 10, None));
+        list.add(Task::new("Task 3".to_string(), 10, None));
+        list.sync_self();
+        list.mark_removed(1).unwrap();
+
+        for task in list.iter_mut() {
+            task.set_order(5);
+        }
+
+        let orders: Vec<i32> = list.items.iter().map(|task| task.order()).collect();
+        assert_eq!(orders, vec![5, 0, 5]);
+        assert_eq!(list.state_counts(), (0, 2, 1));
+    }
+
+    #[test]
+    fn mtc_clone_filtered_keeps_mode_and_skips_removed() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Work 1".to_string(), None));
+        client.add(Todo::new("Home".to_string(), None));
+        client.add(Todo::new("Work 2".to_string(), None));
+        client.add(Todo::new("Work 3".to_string(), None));
+        client.sync_self();
+        client.mark_removed(0).unwrap();
+
+        let work = client.clone_filtered(|todo| todo.body().starts_with("Work"));
+        assert!(!work.is_server());
+        let ids: Vec<usize> = work.items().iter().map(|todo| todo.id()).collect();
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(work.state_counts(), (2, 0, 0));
+        // The source is unchanged.
+        assert_eq!(client.state_counts(), (0, 3, 1));
+
+        let server = client.clone_to_server();
+        let work = server.clone_filtered(|todo| todo.body().starts_with("Work"));
+        assert!(work.is_server());
+        assert_eq!(work.state_counts(), (0, 2, 0));
+    }
+
+    #[test]
+    fn mtc_append_from_moves_matching_items() {
+        let mut source = MtcList::new(false);
+        source.add(Todo::new("Work 1".to_string(), None));
+        source.add(Todo::new("Home".to_string(), None));
+        source.add(Todo::new("Work 2".to_string(), None));
+        source.sync_self();
+        let mut dest = MtcList::new(false);
+
+        let count = dest.append_from(&mut source, |todo| todo.body().starts_with("Work"));
+
+        assert_eq!(count, 2);
+        let bodies = |list: &MtcList<Todo>| -> Vec<String> {
+            list.items()
+                .iter()
+                .map(|todo| todo.body().clone())
+                .collect()
+        };
+        assert_eq!(bodies(&source), vec!["Home"]);
+        assert_eq!(bodies(&dest), vec!["Work 1", "Work 2"]);
+        // The removals are kept on the client so that they are synced.
+        assert_eq!(source.state_counts(), (0, 1, 2));
+        assert_eq!(dest.state_counts(), (2, 0, 0));
+    }
+
+    #[test]
+    fn mtc_replace_all_server_assigns_sequential_ids() {
+        let mut server = MtcList::new(true);
+        server.add(Todo::new("Old".to_string(), None));
+
+        server.replace_all(vec![
+            Todo::new("Todo 1".to_string(), None),
+            Todo::new("Todo 2".to_string(), None),
+            Todo::new("Todo 3".to_string(), None),
+        ]);
+
+        let ids: Vec<usize> = server.items().iter().map(|todo| todo.id()).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(server.state_counts(), (0, 3, 0));
+    }
+
+    #[test]
+    fn mtc_replace_all_client_keeps_removals() {
+        let mut client = MtcList::new(false);
+        client.add(Todo::new("Old".to_string(), None));
+        client.sync_self();
+
+        client.replace_all(vec![
+            Todo::new("Todo 1".to_string(), None),
+            Todo::new("Todo 2".to_string(), None),
+        ]);
+
+        let bodies: Vec<&String> = client.items().iter().map(|todo| todo.body()).collect();
+        assert_eq!(bodies, vec!["Todo 1", "Todo 2"]);
+        assert_eq!(client.state_counts(), (2, 0, 1));
+
+        client.sync_self();
+        let ids: Vec<usize> = client.items().iter().map(|todo| todo.id()).collect();
+        assert_eq!(ids, vec![0, 1]);
+    }
+
     #[test]
     fn mtc_remove_expired_removes_correct() {
         let mut client = MtcList::new(false);
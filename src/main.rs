@@ -1,195 +1,1807 @@
+use std::collections::HashMap;
 use std::env;
 use std::fmt::Display;
 use std::io::{self, BufReader, BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use std::{fs, fs::File};
 
+use fs2::FileExt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use mtc::*;
 
-pub struct Items {
-    pub todos: MtcList<Todo>,
-    pub tasks: MtcList<Task>,
-    pub events: MtcList<Event>,
+/// User settings read from `config.json` in the mtc config directory. Every key is optional.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub week_start: chrono::Weekday,
+    /// Removes todos whose weekday or date has passed when syncing instead of showing them until removed. Todos for any
+    /// day and todos in the inbox are kept.
+    pub expire_todos: bool,
+    /// The longest task duration in minutes accepted when adding or editing tasks.
+    pub max_task_duration: u32,
+    /// Saves expired events to `events-archive.json` in the data directory before they are removed when syncing.
+    pub archive_events: bool,
+    /// An IANA timezone name such as `Europe/Helsinki` used for finding out what today is instead of the system
+    /// timezone. Pinning it keeps machines in different timezones agreeing on today when syncing around midnight.
+    /// The dates shown, exported and parsed from relative arguments use it while the library's own checks such as
+    /// whether an item has expired still use the system date.
+    pub timezone: Option<String>,
+    /// The format of the saved item lists in the data directory. Lists saved in the other format are read and then
+    /// saved in this one so switching keeps the items. Syncing always uses JSON on the server so clients using
+    /// different formats can share one.
+    pub data_format: DataFormat,
+    /// Strings such as emojis shown before the items of each type in `show`. ANSI color codes in them are left out
+    /// when colors are disabled, see `GlobalOptions::use_color`.
+    pub prefixes: Prefixes,
+    /// Saves JSON lists without indentation and line breaks. Compact files are smaller and faster to write but
+    /// harder to edit by hand. Both are read the same way.
+    pub compact_json: bool,
+    /// How the `do` timer is shown.
+    pub timer: TimerSettings,
+    /// Names shown for weekdays in the headers of `show` such as `{"Mon": "Maanantai"}`. Weekdays without a name
+    /// are shown as English three letter abbreviations.
+    pub weekday_names: HashMap<chrono::Weekday, String>,
+}
+
+impl Settings {
+    /// Returns the configured name of the weekday or its English three letter abbreviation.
+    pub fn weekday_name(&self, weekday: chrono::Weekday) -> String {
+        self.weekday_names
+            .get(&weekday)
+            .cloned()
+            .unwrap_or_else(|| weekday.to_string())
+    }
+
+    /// Returns the instant as a date and time in the configured timezone or in the system timezone if none is
+    /// configured. An unknown timezone is reported when the settings are read so it is treated as none here.
+    pub fn local_time(&self, time: chrono::DateTime<chrono::Utc>) -> chrono::NaiveDateTime {
+        match self
+            .timezone
+            .as_deref()
+            .and_then(|tz| tz.parse::<chrono_tz::Tz>().ok())
+        {
+            Some(tz) => time.with_timezone(&tz).naive_local(),
+            None => time.with_timezone(&chrono::Local).naive_local(),
+        }
+    }
+
+    /// Returns the current date and time in the configured timezone. See `local_time`.
+    pub fn now(&self) -> chrono::NaiveDateTime {
+        self.local_time(chrono::Utc::now())
+    }
+
+    /// Returns today in the configured timezone. A day is always from midnight to midnight in the timezone, even
+    /// when DST changes.
+    pub fn today(&self) -> chrono::NaiveDate {
+        self.now().date()
+    }
+}
+
+/// Settings for the task timer.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimerSettings {
+    /// How often the remaining time is redrawn in milliseconds.
+    pub tick_millis: u64,
+    pub format: TimerFormat,
+}
+
+impl Default for TimerSettings {
+    fn default() -> TimerSettings {
+        TimerSettings {
+            tick_millis: 500,
+            format: TimerFormat::Words,
+        }
+    }
+}
+
+/// The format of the remaining time in the task timer.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerFormat {
+    /// `1 h 5 min 30 s`
+    Words,
+    /// `1:05:30`, or `05:30` under an hour.
+    Clock,
+}
+
+/// Prefixes shown before items by type. A missing prefix is empty.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Prefixes {
+    pub todos: String,
+    pub tasks: String,
+    pub events: String,
+}
+
+impl Prefixes {
+    /// Removes the ANSI escape sequences such as colors from every prefix.
+    fn strip_escapes(&mut self) {
+        for prefix in [&mut self.todos, &mut self.tasks, &mut self.events] {
+            *prefix = strip_escapes(prefix);
+        }
+    }
+}
+
+/// Returns the text without ANSI escape sequences. A control sequence is an ESC and `[` followed by parameters and
+/// ends with a letter or another character from `@` to `~`. Other sequences are an ESC and a single character.
+fn strip_escapes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1B' {
+            stripped.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+/// When colors are shown, set with `--color` or `--no-color`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorChoice {
+    /// Colors are shown unless the `NO_COLOR` environment variable is set.
+    Auto,
+    Always,
+    Never,
+}
+
+/// A file format for the saved item lists.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    Json,
+    Toml,
+}
+
+impl DataFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DataFormat::Json => "json",
+            DataFormat::Toml => "toml",
+        }
+    }
+
+    fn other(self) -> DataFormat {
+        match self {
+            DataFormat::Json => DataFormat::Toml,
+            DataFormat::Toml => DataFormat::Json,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            week_start: chrono::Weekday::Mon,
+            expire_todos: false,
+            max_task_duration: 1440,
+            archive_events: false,
+            timezone: None,
+            data_format: DataFormat::Json,
+            prefixes: Prefixes::default(),
+            compact_json: false,
+            timer: TimerSettings::default(),
+            weekday_names: HashMap::new(),
+        }
+    }
+}
+
+/// Options that can be given before or after the command and apply to every command.
+pub struct GlobalOptions {
+    pub data_dir: Option<PathBuf>,
+    /// Prints the steps of a sync to stderr.
+    pub verbose: bool,
+    /// Leaves out the messages confirming what a command did.
+    pub quiet: bool,
+    pub color: ColorChoice,
+}
+
+/// Set from `--quiet` before running a command. Global since confirmations are printed all over the commands.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a message confirming what a command did unless `--quiet` is given. Output the user asked for, such as
+/// shown or exported items, and errors are always printed.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !crate::QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+impl GlobalOptions {
+    /// Parses the global options and removes them from the args so that only the command and its
+    /// arguments are left.
+    pub fn parse(args: &mut Vec<String>) -> Result<GlobalOptions, String> {
+        let mut options = GlobalOptions {
+            data_dir: None,
+            verbose: false,
+            quiet: false,
+            color: ColorChoice::Auto,
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--data-dir" => {
+                    if i + 1 >= args.len() {
+                        return Err("Missing path argument for '--data-dir'.".to_string());
+                    }
+                    options.data_dir = Some(PathBuf::from(args.remove(i + 1)));
+                    args.remove(i);
+                }
+                "--verbose" => {
+                    options.verbose = true;
+                    args.remove(i);
+                }
+                "--quiet" => {
+                    options.quiet = true;
+                    args.remove(i);
+                }
+                "--color" => {
+                    options.color = match args.get(i + 1).map(String::as_str) {
+                        Some("auto") => ColorChoice::Auto,
+                        Some("always") => ColorChoice::Always,
+                        Some("never") => ColorChoice::Never,
+                        Some(when) => {
+                            return Err(format!("Unknown value for '--color': '{}'", when))
+                        }
+                        None => return Err("Missing argument for '--color'.".to_string()),
+                    };
+                    args.remove(i + 1);
+                    args.remove(i);
+                }
+                "--no-color" => {
+                    options.color = ColorChoice::Never;
+                    args.remove(i);
+                }
+                _ => i += 1,
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Returns true if colors are shown. `--color always` shows them even if the `NO_COLOR` environment variable
+    /// is set while `--no-color` and `--color never` always hide them. Otherwise they are hidden if `NO_COLOR` is
+    /// set to anything but an empty string. The last of `--color` and `--no-color` given is used.
+    pub fn use_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => env::var_os("NO_COLOR").map_or(true, |value| value.is_empty()),
+        }
+    }
+
+    /// Returns the directory used for saved items. The `--data-dir` flag takes precedence over the
+    /// `MTC_DATA_DIR` environment variable which takes precedence over the default directory.
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.data_dir {
+            return Some(dir.clone());
+        }
+        if let Some(dir) = env::var_os("MTC_DATA_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        dirs::data_dir().map(|dir| dir.join(Path::new("mtc/")))
+    }
 }
 
 mod commands {
     use chrono::prelude::*;
 
     use crate::commands::set_cmd::set;
-    use readers::read_id;
+    use readers::{read_weekday, resolve_id};
 
     use super::*;
 
-    pub fn handle_command(mut items: Items) -> Items {
-        // There probably is a better way to do this. However the overhead is insignificant so it doesn't matter that much.
-        let args: Vec<String> = env::args().collect();
-        let mut args = args.iter().map(|s| s.as_str());
+    /// Returns the exit code of the process when the command fails:
+    ///
+    /// - 2 when the command or its arguments are invalid
+    /// - 3 when no item matches
+    /// - 4 when syncing fails
+    /// - 5 when accessing a file or the terminal fails
+    ///
+    /// 0 means success and 1 a failure outside of the command such as unreadable settings or items.
+    pub fn exit_code(error: &CommandError) -> i32 {
+        match error {
+            CommandError::UnknownType(_)
+            | CommandError::MissingArg(_)
+            | CommandError::Parse(_)
+            | CommandError::Invalid(_) => EXIT_USAGE,
+            CommandError::NotFound(_) => 3,
+            CommandError::Sync(_) => 4,
+            CommandError::Io(_) => 5,
+        }
+    }
 
-        args.next();
+    /// The exit code used when the command or its arguments are invalid.
+    pub const EXIT_USAGE: i32 = 2;
+
+    /// Runs the command and returns the items with its changes and the exit code of the command.
+    pub fn handle_command(
+        mut items: Items,
+        snapshots: &mut SyncSnapshots,
+        settings: &Settings,
+        options: &GlobalOptions,
+        dir: &Path,
+        args: &[String],
+    ) -> (Items, i32) {
+        let mut args = args.iter().map(|s| s.as_str());
 
-        let result: Result<(), String> = match args.next() {
-            Some("show") => show_cmd::show(&items, args),
+        let result: Result<(), CommandError> = match args.next() {
+            Some("show") => show_cmd::show(&items, settings, args),
             Some("help") => help(),
-            Some("add") => add_cmd::add(&mut items, args),
+            Some("add") => add_cmd::add(&mut items, settings, args),
             Some("remove") => remove(&mut items, args),
-            Some("set") => set(&mut items, args),
-            Some("do") => do_task(&items, args),
-            Some("sync") => sync::sync(&mut items, args),
-            None => Err("Not enough arguments.".to_string()),
-            _ => Err("Unknown command".to_string()),
+            Some("replace") => replace(&mut items, args),
+            Some("set") => set(&mut items, settings, args),
+            Some("move") => move_item(&mut items, args),
+            Some("snooze") => snooze(&mut items, settings, args),
+            Some("reschedule") => reschedule(&mut items, args),
+            Some("export") => export_cmd::export(&items, settings, args),
+            Some("import") => export_cmd::import(&mut items, args),
+            Some("done") => done(&mut items, settings, args),
+            Some("sync") => sync::sync(&mut items, snapshots, settings, options, dir, args),
+            // Not shown in the help since it is only meant for diagnosing sync issues.
+            Some("debug") => debug(&items, args),
+            None => Err(CommandError::MissingArg(
+                "Not enough arguments.".to_string(),
+            )),
+            _ => Err(CommandError::Invalid("Unknown command".to_string())),
         };
 
-        if let Err(e) = result {
-            eprintln!("{}", e);
-            println!("Use: 'mtc help' for help.");
+        (items, report_error(result))
+    }
+
+    /// Prints the error of a failed command and returns the exit code.
+    fn report_error(result: Result<(), CommandError>) -> i32 {
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                println!("Use: 'mtc help' for help.");
+                exit_code(&e)
+            }
+        }
+    }
+
+    /// Runs `show` again whenever a saved list changes or the interval passes, until Ctrl-C is pressed. The data
+    /// lock is only held while reading so that syncs and other commands can change the items meanwhile.
+    pub fn watch(settings: &Settings, dir: &Path, args: &[String]) -> i32 {
+        report_error(watch_show(settings, dir, args))
+    }
+
+    fn watch_show(settings: &Settings, dir: &Path, args: &[String]) -> Result<(), CommandError> {
+        let mut args: Vec<&str> = args
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|arg| *arg != "--watch")
+            .collect();
+        // Redrawing once a minute by default keeps "today" correct after midnight even if nothing changes.
+        let mut interval = Duration::from_secs(60);
+        if let Some(i) = args.iter().position(|arg| *arg == "--interval") {
+            let secs: u64 = args
+                .get(i + 1)
+                .ok_or_else(|| {
+                    CommandError::MissingArg(
+                        "Missing seconds argument for '--interval'.".to_string(),
+                    )
+                })?
+                .parse()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| {
+                    CommandError::Parse(
+                        "Interval must be a positive number of seconds.".to_string(),
+                    )
+                })?;
+            interval = Duration::from_secs(secs);
+            args.drain(i..i + 2);
         }
 
-        items
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handler_stopped = Arc::clone(&stopped);
+        ctrlc::set_handler(move || handler_stopped.store(true, Ordering::SeqCst)).map_err(|e| {
+            CommandError::Io(format!("Failed to set a Ctrl-C handler.\nReason: {}", e))
+        })?;
+
+        loop {
+            let modified = list_mtimes(dir, settings.data_format);
+            let items =
+                DataLock::acquire(dir).and_then(|_lock| read_items(dir, settings.data_format));
+
+            // Clears the screen and moves the cursor to the top left corner.
+            print!("\x1B[2J\x1B[H");
+            match items {
+                // Invalid arguments won't become valid on the next round so the watch stops.
+                Ok(items) => show_cmd::show(&items, settings, args.iter().copied())?,
+                // A sync may be in the middle of writing the files so this is retried on the next change.
+                Err(msg) => eprintln!("Reading saved items failed.\n{}", msg),
+            }
+            io::stdout().flush().expect("Failed to flush stdout.");
+
+            let started = Instant::now();
+            while list_mtimes(dir, settings.data_format) == modified && started.elapsed() < interval
+            {
+                if stopped.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+        }
+    }
+
+    /// Returns the modification times of the saved lists. A missing list has no time.
+    fn list_mtimes(dir: &Path, format: DataFormat) -> Vec<Option<std::time::SystemTime>> {
+        ["todos", "tasks", "events"]
+            .iter()
+            .map(|name| {
+                fs::metadata(saved_list_path(dir, name, format))
+                    .and_then(|meta| meta.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Runs `do` which takes the data lock only while reading and saving the items, since the timer can run for
+    /// hours and would otherwise block every other invocation, including syncs.
+    pub fn do_unlocked(settings: &Settings, dir: &Path, args: &[String]) -> i32 {
+        report_error(do_task(settings, dir, args.iter().map(|s| s.as_str())))
     }
 
-    fn help() -> Result<(), String> {
+    /// Runs `triage` which takes the data lock only while reading and saving the items, since waiting for the
+    /// answers would otherwise block every other invocation.
+    pub fn triage_unlocked(settings: &Settings, dir: &Path) -> i32 {
+        report_error(triage(settings, dir))
+    }
+
+    /// Reads the items holding the data lock only while reading.
+    fn read_locked(settings: &Settings, dir: &Path) -> Result<Items, CommandError> {
+        let _lock = DataLock::acquire(dir).map_err(CommandError::Io)?;
+        read_items(dir, settings.data_format)
+            .map_err(|e| CommandError::Io(format!("Reading saved items failed.\n{}", e)))
+    }
+
+    /// Reads the items, changes them and saves them while holding the data lock. The items are read again so that
+    /// the changes other invocations made meanwhile are kept.
+    fn update_locked<F>(settings: &Settings, dir: &Path, change: F) -> Result<(), CommandError>
+    where
+        F: FnOnce(&mut Items) -> Result<(), CommandError>,
+    {
+        let _lock = DataLock::acquire(dir).map_err(CommandError::Io)?;
+        let mut items = read_items(dir, settings.data_format)
+            .map_err(|e| CommandError::Io(format!("Reading saved items failed.\n{}", e)))?;
+        change(&mut items)?;
+        write_items(dir, items, settings)
+            .map_err(|e| CommandError::Io(format!("Writing items failed.\n{}", e)))
+    }
+
+    fn help() -> Result<(), CommandError> {
         println!("MTC - My Time Contract - a CLI time management app.");
-        println!("usage: mtc <command> [<args>]");
+        println!("usage: mtc [--data-dir <path>] [--verbose] [--quiet] [--color <when> | --no-color] <command> [<args>]");
         println!("Read the README.md for more information");
         println!();
+        println!("Options:");
+        println!("\t--data-dir <path>");
+        println!("\tUses the given directory for saved items instead of the default one. The MTC_DATA_DIR environment variable can be used as well.\n");
+        println!("\t--verbose");
+        println!("\tPrints every step of a sync to stderr.\n");
+        println!("\t--quiet");
+        println!("\tLeaves out the messages confirming what a command did. Requested output such as shown items and errors are still printed.\n");
+        println!("\t--color (auto | always | never), --no-color");
+        println!("\tShows or hides colors in the configured prefixes. '--no-color' is the same as '--color never'. With 'auto', the default, colors are hidden if the NO_COLOR environment variable is set. 'always' shows them even if NO_COLOR is set.\n");
         println!("Commands:");
-        println!("\tshow [<type> | <weekday> | today | tomorrow | overview | month]");
-        println!("\tShows saved items.\n");
-        println!("\tadd <type> <body> (duration) ([weekday] | <date>)");
-        println!("\tAdds a item of a given type. Todos and tasks accept a weekday, events a date. Weekday can be optionally left out. Duration is only used for tasks.\n");
-        println!("\tremove <type> <id>");
-        println!("\tRemoves a item of a given type.\n");
-        println!("\tset <type> <id> <property> <value>");
-        println!("\tSets the value of a property of a item. For example 'set todo 1 body hello' sets the body of the todo with the id 1 to 'hello'. Note that this will change the id of the item.\n");
-        println!("\tdo <task id>");
-        println!("\tShows a timer for a task.\n");
-        println!("\tsync [self | overwrite]");
-        println!("\tSyncs all items with a server specified by a config. Using 'self' or 'overwrite' isn't usually necessary.\n");
+        println!("\tshow [<type> | <weekday> | today [--agenda] | now | tomorrow | overview | week [--from-today] | month | next <days> [--hide-empty] | duplicates | inbox | expired | pending]");
+        println!("\tShows saved items. 'events' accepts '--since <date>' and '--until <date>' filters and '--show-overdue' shows the past events that haven't expired yet first. 'week' starts on the configured 'week_start' day unless '--from-today' is given. '--agenda' shows today's items as one list. 'now' shows the events happening right now along with today's tasks and todos. 'next' shows the given number of days starting today and '--hide-empty' leaves out days without items. 'expired' shows the items that the next sync removes. 'pending' shows when each type was last synced and the items added (+) or removed (-) since. '--context <context>' shows only the todos and tasks of a context.\n");
+        println!("\tshow ... --watch [--interval <seconds>]");
+        println!("\tShows the items again whenever the saved items change and every 60 seconds or the given interval. Ctrl-C stops watching.\n");
+        println!("\tshow ... --format <template>");
+        println!("\tShows one line per item using a template such as '{{id}}\\t{{body}}'. Every type has '{{id}}', '{{body}}' and '{{uuid}}', todos and events '{{date}}' and '{{weekday}}', todos and tasks '{{context}}', tasks '{{duration}}' and '{{weekdays}}' and events '{{location}}'.\n");
+        println!("\tadd <type> <body> (duration) ([weekday] | <date> [--at <location>] [--time <HH:MM-HH:MM>] [--yearly | --since-year <year>] | --every <days>) [--context <context>]");
+        println!("\tAdds a item of a given type. Todos and tasks accept a weekday, events a date. Weekday can be optionally left out. Duration is only used for tasks. Todos can repeat every given amount of days with '--every' instead of a weekday. 'add todo --stdin' adds every non-empty line of stdin as a todo instead of a body. Events can have a location given with '--at' and start and end times given with '--time'. '--yearly' repeats an event every year on its date and '--since-year' also shows the years since the given year, such as the age on a birthday. Todos and tasks can have a single word context such as 'home' given with '--context'. Dates are given as YYYY-MM-DD or relative to today as 'today', 'tomorrow', 'yesterday', '+<n>d', '+<n>w' or 'next <weekday>'.\n");
+        println!("\tadd quick <body>");
+        println!("\tAdds a todo without a weekday to the inbox. Setting the weekday of the todo removes it from the inbox.\n");
+        println!("\tremove <type> (<id> | --weekday <weekday>) [--dry-run]");
+        println!("\tRemoves a item of a given type or all items of the type for a weekday. '--dry-run' only shows what would be removed.\n");
+        println!("\treplace <old> <new> [--type <type>] [--dry-run]");
+        println!("\tReplaces the text in the bodies of every item or only the items of a type. Items that become the same as another item are warned about. '--dry-run' only shows what would change. Note that this will change the ids of the changed items.\n");
+        println!("\tset <type> <id> <property> <value> [<property> <value>...]");
+        println!("\tSets the value of a property of a item. For example 'set todo 1 body hello' sets the body of the todo with the id 1 to 'hello'. Multiple properties can be set at once and nothing is changed if any of them is invalid. Events also have a 'location' property and todos and tasks a 'context' property which are cleared if no value is given. Note that this will change the id of the item.\n");
+        println!("\tsnooze (todo | event) <id> <date>");
+        println!("\tMoves a todo or an event to a later date such as 'tomorrow' or '+3d'. Note that this will change the id of the item.\n");
+        println!("\treschedule <weekday> <weekday> [--type (todo | task)]");
+        println!("\tMoves every todo and task or only the items of a type from the first weekday to the second. Repeating todos, todos for any day, todos in the inbox and todos whose day has passed are left as they are. Note that this will change the ids of the moved items.\n");
+        println!("\tmove <type> <id> (up | down)");
+        println!("\tMoves a item one place up or down in the displayed order, past its neighbour. Events are only reordered among the events with the same date and time. Note that this will change the ids of the moved items.\n");
+        println!(
+            "\texport (jsonl (<type> | all) | csv <type> | ical | markdown [week] | all <dir>)"
+        );
+        println!("\tWrites every item of a type to stdout as one JSON object per line or as CSV with the '--format' fields as columns. 'jsonl all' writes the items of every type with the type in a 'type' field. 'ical' writes the events as an iCalendar file. 'markdown' writes a table of the next seven days with a column per weekday from monday to sunday, or of the current week with 'week'. 'all' writes backup.json with every item, calendar.ics and a CSV file per type to the directory.\n");
+        println!("\timport jsonl (<type> | all)");
+        println!("\tAdds items of a type read from stdin as one JSON object per line. With 'all' each object has its type in a 'type' field like 'export jsonl all' writes. Nothing is added if a line is invalid.\n");
+        println!("\ttriage");
+        println!("\tGoes through the todos in the inbox one at a time and asks for a weekday for each. An empty answer skips the todo and 'q' stops. Every answer is saved right away.\n");
+        println!("\tdo <task id> [minutes]");
+        println!("\tShows a timer for a task. The timer runs for the given minutes instead of the task's duration if they are given. The task is marked done for today when the timer finishes.\n");
+        println!("\tdone <task id> [date]");
+        println!("\tMarks a task done for today or the given date. The task still repeats on its other days. Done tasks are marked with [done] in 'show'. Note that this will change the id of the task.\n");
+        println!("\tAn id can be replaced with a part of the item's body as long as only one item matches it.\n");
+        println!("\tsync [self | overwrite] [--only <type>...] [--config <path>]");
+        println!("\tSyncs all items with a server specified by a config. Using 'self' or 'overwrite' isn't usually necessary. '--only' syncs only the given types and can be given multiple times. '--config' reads the sync config from the given file instead of the default one. The password is read from the MTC_PASSWORD environment variable if it is set instead of asking for it. Lists that haven't changed on either side since the last sync aren't transferred. Syncing keeps the order of the items: removed items are left out so the ids after them shrink and items from the server are added after the others. The lists on the server are saved sorted.\n");
         println!("\thelp");
-        println!("\tShows this help output.");
+        println!("\tShows this help output.\n");
+        println!("Exit codes:");
+        println!("\t0 success, 1 reading or writing settings or items failed, 2 invalid command or arguments, 3 no matching item, 4 sync failed, 5 file or terminal access failed");
         Ok(())
     }
 
-    fn do_task<'a, T>(items: &Items, mut args: T) -> Result<(), String>
+    fn do_task<'a, T>(settings: &Settings, dir: &Path, mut args: T) -> Result<(), CommandError>
     where
         T: Iterator<Item = &'a str>,
     {
-        // This will be soon changed completely so it is not yet refactored to the new result based
-        // error handling.
-        let id = read_id(args.next())?;
+        let items = read_locked(settings, dir)?;
+        let id = resolve_id(&items.tasks, args.next())?;
+        // The override is only used for this timer and doesn't change the task.
+        let duration_override = match args.next() {
+            Some(minutes) => Some(readers::read_duration(
+                Some(minutes),
+                settings.max_task_duration,
+            )?),
+            None => None,
+        };
+
+        if let Some(task) = items.tasks.get_by_id(id).cloned() {
+            let duration = duration_override.unwrap_or(task.duration());
+            // Tasks can have no duration if they were added before durations were validated.
+            if duration == 0 {
+                return Err(CommandError::Invalid(
+                    "The task has no duration. Set one with 'set task <id> duration <minutes>' or give the minutes after the id."
+                        .to_string(),
+                ));
+            }
 
-        if let Some(task) = items.tasks.items().iter().find(|item| item.id() == id) {
-            let mut millis_left = task.duration() as u128 * 60_000;
+            // Ctrl-C stops the timer instead of killing the process so that the items are still saved.
+            let stopped = Arc::new(AtomicBool::new(false));
+            let handler_stopped = Arc::clone(&stopped);
+            ctrlc::set_handler(move || handler_stopped.store(true, Ordering::SeqCst)).map_err(
+                |e| CommandError::Io(format!("Failed to set a Ctrl-C handler.\nReason: {}", e)),
+            )?;
+
+            let format = settings.timer.format;
+            // A tick of 0 would redraw as fast as possible which only wastes CPU.
+            let tick = Duration::from_millis(settings.timer.tick_millis.max(10));
+            let total_millis = duration as u128 * 60_000;
+            let mut millis_left = total_millis;
             loop {
                 let now = Instant::now();
-                // "Clear" the line.
-                print!("\r                                 ");
-                let seconds_left = millis_left / 1000;
-                let hours = seconds_left / 3600;
-                let minutes = (seconds_left - hours * 3600) / 60;
-                let seconds = seconds_left - hours * 3600 - minutes * 60;
-                print!("\rTime left: {} h {} min {} s", hours, minutes, seconds);
+                // The line is overwritten in one write padded with spaces so that shorter lines leave nothing behind.
+                // Clearing it first and then writing makes some terminals flicker.
+                print_timer_line(&format!(
+                    "Time left: {}",
+                    format_millis(millis_left, format)
+                ));
                 io::stdout().flush().expect("Failed to flush stdout.");
-                thread::sleep(Duration::from_millis(500));
+                thread::sleep(tick);
                 if let Some(n) = millis_left.checked_sub(now.elapsed().as_millis()) {
                     millis_left = n;
                 } else {
                     // Print here this one last time since the timer could otherwise stop at 0 h 0 min 1 s for example
                     // which is quite annoying.
-                    println!("\rTime left: 0 h 0 min 0 s");
+                    print_timer_line(&format!("Time left: {}", format_millis(0, format)));
+                    println!();
+                    // The task is looked up again by its uuid since other invocations may have changed the ids.
+                    return update_locked(settings, dir, |items| {
+                        let task =
+                            items
+                                .tasks
+                                .get_by_uuid(task.uuid())
+                                .cloned()
+                                .ok_or_else(|| {
+                                    CommandError::NotFound(
+                                        "The task was removed while the timer ran.".to_string(),
+                                    )
+                                })?;
+                        mark_task_done(items, task, settings.today());
+                        Ok(())
+                    });
+                }
+                if stopped.load(Ordering::SeqCst) {
+                    // Overwrite the line again since the terminal may have echoed ^C on it.
+                    print_timer_line(&format!(
+                        "Stopped after {}.",
+                        format_millis(total_millis - millis_left, format)
+                    ));
+                    println!();
                     return Ok(());
                 }
             }
         } else {
-            eprintln!("No task with the given ID found.");
+            Err(CommandError::NotFound(
+                "No task with the given ID found.".to_string(),
+            ))
+        }
+    }
+
+    /// Asks for a weekday for every todo in the inbox. Unlike the other commands this is interactive since going
+    /// through the todos one at a time is the point of it.
+    fn triage(settings: &Settings, dir: &Path) -> Result<(), CommandError> {
+        use std::io::BufRead;
+
+        let items = read_locked(settings, dir)?;
+        let mut inbox: Vec<Todo> = items
+            .todos
+            .items()
+            .into_iter()
+            .filter(|todo| todo.is_inbox())
+            .cloned()
+            .collect();
+        inbox.sort();
+        if inbox.is_empty() {
+            println!("The inbox is empty.");
+            return Ok(());
+        }
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        for todo in inbox {
+            println!("{:#}", todo);
+            let weekday = loop {
+                print!("Weekday (empty to skip, 'q' to stop): ");
+                io::stdout().flush().expect("Failed to flush stdout.");
+                let line = match lines.next() {
+                    Some(line) => line.map_err(|e| {
+                        CommandError::Io(format!("Failed to read the answer.\nReason: {}", e))
+                    })?,
+                    // End of input stops like 'q'.
+                    None => {
+                        println!();
+                        return Ok(());
+                    }
+                };
+                match line.trim() {
+                    "" => break None,
+                    "q" => return Ok(()),
+                    answer => match readers::parse_weekday(answer) {
+                        Ok(weekday) => break Some(weekday),
+                        Err(e) => eprintln!("{}", e),
+                    },
+                }
+            };
+
+            if let Some(weekday) = weekday {
+                // Saved after every answer so that stopping midway keeps the progress.
+                update_locked(settings, dir, |items| {
+                    // Looked up again by the uuid since other invocations may have changed the ids meanwhile.
+                    let old = match items.todos.get_by_uuid(todo.uuid()) {
+                        Some(old) => old.clone(),
+                        None => {
+                            eprintln!("'{}' was removed meanwhile.", todo.body());
+                            return Ok(());
+                        }
+                    };
+                    // Setting the weekday takes the todo out of the inbox like 'set' does.
+                    let mut new = old.clone();
+                    new.set_weekday(Some(weekday));
+                    new.set_inbox(false);
+                    items.todos.mark_removed(old.id()).unwrap();
+                    items.todos.add(new);
+                    Ok(())
+                })?;
+            }
         }
         Ok(())
     }
 
-    fn remove<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+    /// Checks a task off for a date. Like other edits this replaces the task. Syncing then replaces the copies on
+    /// other clients with the server's version, so the date reaches them too.
+    fn mark_task_done(items: &mut Items, mut task: Task, date: NaiveDate) {
+        items.tasks.mark_removed(task.id()).unwrap();
+        task.mark_done(date);
+        items.tasks.add(task);
+    }
+
+    fn done<'a, T>(items: &mut Items, settings: &Settings, mut args: T) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        let id = resolve_id(&items.tasks, args.next())?;
+        let mut args = args.peekable();
+        let date = if args.peek().is_some() {
+            readers::read_date(&mut args, settings.today())?
+        } else {
+            settings.today()
+        };
+        let task = items.tasks.get_by_id(id).cloned().ok_or_else(|| {
+            CommandError::NotFound("No task with the given id found.".to_string())
+        })?;
+        if task.is_done_on(date) {
+            info!(
+                "'{}' is already done on {} {}.",
+                task.body(),
+                date.weekday(),
+                date
+            );
+            return Ok(());
+        }
+        info!(
+            "Marked '{}' done on {} {}.",
+            task.body(),
+            date.weekday(),
+            date
+        );
+        mark_task_done(items, task, date);
+        Ok(())
+    }
+
+    fn print_timer_line(line: &str) {
+        print!("\r{:<33}", line);
+    }
+
+    fn format_millis(millis: u128, format: TimerFormat) -> String {
+        let seconds = millis / 1000;
+        let hours = seconds / 3600;
+        let minutes = (seconds - hours * 3600) / 60;
+        let seconds = seconds - hours * 3600 - minutes * 60;
+        match format {
+            TimerFormat::Words => format!("{} h {} min {} s", hours, minutes, seconds),
+            TimerFormat::Clock if hours > 0 => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+            TimerFormat::Clock => format!("{:02}:{:02}", minutes, seconds),
+        }
+    }
+
+    fn debug<'a, T>(items: &Items, mut args: T) -> Result<(), CommandError>
     where
         T: Iterator<Item = &'a str>,
     {
         match args.next() {
-            Some("todo") => {
-                let id = read_id(args.next())?;
-                items.todos.mark_removed(id)?;
+            Some("states") => {
+                println!("\tNew\tNeutral\tRemoved");
+                print_state_counts("Todos", &items.todos);
+                print_state_counts("Tasks", &items.tasks);
+                print_state_counts("Events", &items.events);
+                Ok(())
+            }
+            Some("check") => {
+                let results = [
+                    ("Todos", items.todos.check_invariants()),
+                    ("Tasks", items.tasks.check_invariants()),
+                    ("Events", items.events.check_invariants()),
+                ];
+                let mut failed = false;
+                for (name, result) in results {
+                    match result {
+                        Ok(()) => println!("{}: ok", name),
+                        Err(msg) => {
+                            println!("{}: {}", name, msg);
+                            failed = true;
+                        }
+                    }
+                }
+                if failed {
+                    Err(CommandError::Invalid(
+                        "The saved items are inconsistent.".to_string(),
+                    ))
+                } else {
+                    Ok(())
+                }
             }
+            Some(arg) => Err(CommandError::Invalid(format!(
+                "Unknown argument: '{}'",
+                arg
+            ))),
+            None => Err(CommandError::MissingArg(
+                "Missing debug command argument.".to_string(),
+            )),
+        }
+    }
+
+    fn print_state_counts<T: MtcItem + Clone>(name: &str, list: &MtcList<T>) {
+        let (new, neutral, removed) = list.state_counts();
+        println!("{}\t{}\t{}\t{}", name, new, neutral, removed);
+    }
+
+    /// Moves a todo or an event to a later date. The date can be anything `read_date` accepts.
+    fn snooze<'a, T>(
+        items: &mut Items,
+        settings: &Settings,
+        mut args: T,
+    ) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        let today = settings.today();
+        let typ = args.next();
+        let (id, date) = match typ {
+            Some("todo") => (
+                resolve_id(&items.todos, args.next())?,
+                readers::read_date(&mut args, today)?,
+            ),
+            Some("event") => (
+                resolve_id(&items.events, args.next())?,
+                readers::read_date(&mut args, today)?,
+            ),
             Some("task") => {
-                let id = read_id(args.next())?;
-                items.tasks.mark_removed(id)?;
+                return Err(CommandError::Invalid(
+                    "Tasks repeat weekly and cannot be snoozed.".to_string(),
+                ))
+            }
+            Some(typ) => return Err(CommandError::UnknownType(typ.to_string())),
+            None => return Err(CommandError::MissingArg("No type specified".to_string())),
+        };
+        if date < today {
+            return Err(CommandError::Invalid(
+                "Cannot snooze to a date in the past.".to_string(),
+            ));
+        }
+
+        let not_found = || CommandError::NotFound("No item with the given id found.".to_string());
+        let body = if typ == Some("todo") {
+            let old = items.todos.get_by_id(id).ok_or_else(not_found)?;
+            // A weekday todo becomes a dated one so that it isn't shown again before the date.
+            let mut new = old.clone();
+            new.set_date(date);
+            new.set_inbox(false);
+            items.todos.mark_removed(id).unwrap();
+            items.todos.add(new.clone());
+            new.body().clone()
+        } else {
+            let mut new = items.events.get_by_id(id).ok_or_else(not_found)?.clone();
+            new.set_date(date);
+            items.events.mark_removed(id).unwrap();
+            items.events.add(new.clone());
+            new.body().clone()
+        };
+        info!("Snoozed '{}' to {} {}.", body, date.weekday(), date);
+        Ok(())
+    }
+
+    /// Moves every todo and task of a weekday to another weekday, for example when swapping the routines of two days.
+    fn reschedule<'a, T>(items: &mut Items, args: T) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        let mut args: Vec<&str> = args.collect();
+        let typ = match args.iter().position(|arg| *arg == "--type") {
+            Some(i) => {
+                let typ = args.get(i + 1).copied().ok_or_else(|| {
+                    CommandError::MissingArg("Missing type argument for '--type'.".to_string())
+                })?;
+                match typ {
+                    "todo" | "task" => {}
+                    "event" => {
+                        return Err(CommandError::Invalid(
+                            "Events are for a date and cannot be rescheduled by weekday. Use 'snooze' instead."
+                                .to_string(),
+                        ))
+                    }
+                    typ => return Err(CommandError::UnknownType(typ.to_string())),
+                }
+                args.drain(i..i + 2);
+                Some(typ)
+            }
+            None => None,
+        };
+        let mut args = args.into_iter();
+        let missing = || CommandError::MissingArg("Missing weekday argument.".to_string());
+        let from = read_weekday(args.next())?.ok_or_else(missing)?;
+        let to = read_weekday(args.next())?.ok_or_else(missing)?;
+        if let Some(arg) = args.next() {
+            return Err(CommandError::Invalid(format!(
+                "Unknown argument: '{}'",
+                arg
+            )));
+        }
+        if from == to {
+            return Err(CommandError::Invalid(
+                "The weekdays must be different.".to_string(),
+            ));
+        }
+
+        let selected = |name: &str| typ.map_or(true, |typ| typ == name);
+        let mut count = 0;
+        if selected("todo") {
+            // Todos for any day, repeating todos and todos whose day has passed aren't for a weekday.
+            count += items.todos.update_where(
+                |todo| todo_for_weekday(todo, from),
+                |todo| todo.set_weekday(Some(to)),
+            );
+        }
+        if selected("task") {
+            // Tasks without weekdays are for every day so they stay as they are.
+            count += items.tasks.update_where(
+                |task| task.weekdays()[from.num_days_from_monday() as usize],
+                |task| {
+                    task.set_for_weekday(from, false);
+                    task.set_for_weekday(to, true);
+                },
+            );
+        }
+        info!("Moved {} items from {} to {}.", count, from, to);
+        Ok(())
+    }
+
+    fn remove<'a, T>(items: &mut Items, mut args: T) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        match args.next() {
+            Some("todo") => remove_from(&mut items.todos, args, todo_for_weekday),
+            Some("task") => remove_from(&mut items.tasks, args, Task::for_weekday),
+            Some("event") => remove_from(&mut items.events, args, Event::for_weekday),
+            Some(typ) => Err(CommandError::UnknownType(typ.to_string())),
+            None => Err(CommandError::MissingArg("No type specified".to_string())),
+        }
+    }
+
+    /// Returns true if the todo is for the weekday itself. `MtcItem::for_weekday` would also match the todos shown on
+    /// the weekday without being for it, such as todos for any day or todos whose day has passed when the weekday is
+    /// today.
+    fn todo_for_weekday(todo: &Todo, weekday: Weekday) -> bool {
+        todo.weekday() == Some(weekday)
+    }
+
+    fn remove_from<'a, T, I>(
+        list: &mut MtcList<T>,
+        args: I,
+        for_weekday: fn(&T, Weekday) -> bool,
+    ) -> Result<(), CommandError>
+    where
+        T: MtcItem + Clone + Ord + Display,
+        I: Iterator<Item = &'a str>,
+    {
+        let mut args: Vec<&str> = args.collect();
+        let dry_run = match args.iter().position(|arg| *arg == "--dry-run") {
+            Some(i) => {
+                args.remove(i);
+                true
+            }
+            None => false,
+        };
+        let mut args = args.into_iter();
+
+        match args.next() {
+            Some("--weekday") => {
+                let weekday = match read_weekday(args.next())? {
+                    Some(wd) => wd,
+                    None => {
+                        return Err(CommandError::MissingArg(
+                            "Missing weekday argument.".to_string(),
+                        ))
+                    }
+                };
+                if dry_run {
+                    let mut matching: Vec<&T> = list
+                        .items()
+                        .into_iter()
+                        .filter(|item| for_weekday(item, weekday))
+                        .collect();
+                    if matching.is_empty() {
+                        println!("No items for {} would be removed.", weekday);
+                    } else {
+                        matching.sort();
+                        println!("Would remove {} items:", matching.len());
+                        for item in matching {
+                            println!("\t{}", item);
+                        }
+                    }
+                    return Ok(());
+                }
+                let count = list.mark_removed_where(|item| for_weekday(item, weekday));
+                info!("Removed {} items.", count);
+            }
+            next => {
+                let id = resolve_id(list, next)?;
+                if dry_run {
+                    let item = list.get_by_id(id).ok_or_else(|| {
+                        CommandError::NotFound("No item with the given id found.".to_string())
+                    })?;
+                    println!("Would remove: {}", item);
+                    return Ok(());
+                }
+                list.mark_removed(id)
+                    .map_err(|e| CommandError::NotFound(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn replace<'a, T>(items: &mut Items, args: T) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        let mut args: Vec<&str> = args.collect();
+        let dry_run = match args.iter().position(|arg| *arg == "--dry-run") {
+            Some(i) => {
+                args.remove(i);
+                true
+            }
+            None => false,
+        };
+        let typ = match args.iter().position(|arg| *arg == "--type") {
+            Some(i) => {
+                let typ = args.get(i + 1).copied().ok_or_else(|| {
+                    CommandError::MissingArg("Missing type argument for '--type'.".to_string())
+                })?;
+                if !["todo", "task", "event"].contains(&typ) {
+                    return Err(CommandError::UnknownType(typ.to_string()));
+                }
+                args.drain(i..i + 2);
+                Some(typ)
+            }
+            None => None,
+        };
+        let mut args = args.into_iter();
+        let old = args
+            .next()
+            .filter(|old| !old.is_empty())
+            .ok_or_else(|| CommandError::MissingArg("Missing text to replace.".to_string()))?;
+        let new = args
+            .next()
+            .ok_or_else(|| CommandError::MissingArg("Missing replacement text.".to_string()))?;
+        if let Some(arg) = args.next() {
+            return Err(CommandError::Invalid(format!(
+                "Unknown argument: '{}'",
+                arg
+            )));
+        }
+
+        let selected = |name: &str| typ.map_or(true, |typ| typ == name);
+        let mut count = 0;
+        if selected("todo") {
+            count += replace_in(&mut items.todos, old, new, dry_run, Todo::set_body);
+        }
+        if selected("task") {
+            count += replace_in(&mut items.tasks, old, new, dry_run, Task::set_body);
+        }
+        if selected("event") {
+            count += replace_in(&mut items.events, old, new, dry_run, Event::set_body);
+        }
+        if dry_run {
+            println!("Would change {} items.", count);
+        } else {
+            info!("Changed {} items.", count);
+        }
+        Ok(())
+    }
+
+    /// Replaces `old` with `new` in the bodies of the items containing it and returns how many items changed. The
+    /// changed items are shown and items that become identical to another item are warned about.
+    fn replace_in<T>(
+        list: &mut MtcList<T>,
+        old: &str,
+        new: &str,
+        dry_run: bool,
+        set_body: fn(&mut T, String),
+    ) -> usize
+    where
+        T: MtcItem + Clone + Display,
+    {
+        let replaced = |item: &T| {
+            let mut item = item.clone();
+            let body = item.body().replace(old, new);
+            set_body(&mut item, body);
+            item
+        };
+        let before = list.items();
+        let after: Vec<T> = before
+            .iter()
+            .map(|item| {
+                if item.body().contains(old) {
+                    replaced(item)
+                } else {
+                    (*item).clone()
+                }
+            })
+            .collect();
+
+        let mut count = 0;
+        for (i, item) in before.iter().enumerate() {
+            if !item.body().contains(old) {
+                continue;
+            }
+            count += 1;
+            let changed = &after[i];
+            if dry_run {
+                println!("\t{} -> {:#}", item, changed);
+            }
+            let duplicate = after
+                .iter()
+                .enumerate()
+                .any(|(j, other)| j != i && other.ignore_state_eq(changed));
+            if duplicate {
+                eprintln!("Warning: '{:#}' is the same as another item.", changed);
+            }
+        }
+
+        if !dry_run {
+            list.update_where(
+                |item| item.body().contains(old),
+                |item| {
+                    let body = item.body().replace(old, new);
+                    set_body(item, body);
+                },
+            );
+        }
+        count
+    }
+
+    fn move_item<'a, T>(items: &mut Items, mut args: T) -> Result<(), CommandError>
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        match args.next() {
+            Some("todo") => move_in(
+                &mut items.todos,
+                args,
+                Todo::order,
+                Todo::set_order,
+                |_, _| true,
+            ),
+            Some("task") => move_in(
+                &mut items.tasks,
+                args,
+                Task::order,
+                Task::set_order,
+                |_, _| true,
+            ),
+            // The manual order only sorts events with the same date and time.
+            Some("event") => move_in(
+                &mut items.events,
+                args,
+                Event::order,
+                Event::set_order,
+                |a, b| a.date() == b.date() && a.time() == b.time(),
+            ),
+            Some(typ) => Err(CommandError::UnknownType(typ.to_string())),
+            None => Err(CommandError::MissingArg("No type specified".to_string())),
+        }
+    }
+
+    /// Moves the item past its neighbour among the items sorted together with it. The items are renumbered in their
+    /// new order since items with the same order are sorted by body and swapping their orders would change nothing.
+    fn move_in<'a, T, I>(
+        list: &mut MtcList<T>,
+        mut args: I,
+        order: fn(&T) -> i32,
+        set_order: fn(&mut T, i32),
+        sorted_together: fn(&T, &T) -> bool,
+    ) -> Result<(), CommandError>
+    where
+        T: MtcItem + Clone + Ord,
+        I: Iterator<Item = &'a str>,
+    {
+        let id = resolve_id(list, args.next())?;
+        let up = match args.next() {
+            Some("up") => true,
+            Some("down") => false,
+            Some(dir) => {
+                return Err(CommandError::Invalid(format!(
+                    "Unknown direction: '{}'",
+                    dir
+                )))
+            }
+            None => {
+                return Err(CommandError::MissingArg(
+                    "Missing direction argument.".to_string(),
+                ))
+            }
+        };
+
+        let item = match list.get_by_id(id) {
+            Some(item) => item.clone(),
+            None => {
+                return Err(CommandError::NotFound(
+                    "No item with the given id found.".to_string(),
+                ))
+            }
+        };
+        let mut group: Vec<T> = list
+            .items()
+            .into_iter()
+            .filter(|other| sorted_together(&item, other))
+            .cloned()
+            .collect();
+        group.sort();
+        let position = group.iter().position(|other| other.id() == id).unwrap();
+        let neighbour = if up {
+            position.checked_sub(1)
+        } else {
+            Some(position + 1).filter(|next| *next < group.len())
+        };
+        let neighbour = match neighbour {
+            Some(neighbour) => neighbour,
+            None => {
+                info!(
+                    "'{}' is already at the {}.",
+                    item.body(),
+                    if up { "top" } else { "bottom" }
+                );
+                return Ok(());
             }
-            Some("event") => {
-                let id = read_id(args.next())?;
-                items.events.mark_removed(id)?;
+        };
+        group.swap(position, neighbour);
+
+        // Readding the changed items makes the new order propagate through syncing like any other edit. Removed items
+        // stay in place so the ids of the group stay valid while readding.
+        for (index, mut other) in group.into_iter().enumerate() {
+            let index = index as i32;
+            if order(&other) != index {
+                let other_id = other.id();
+                set_order(&mut other, index);
+                list.mark_removed(other_id)
+                    .map_err(|e| CommandError::NotFound(e.to_string()))?;
+                list.add(other);
+            }
+        }
+        Ok(())
+    }
+
+    mod export_cmd {
+        use std::io::BufRead;
+
+        use super::template::TemplateFields;
+        use super::*;
+
+        pub fn export<'a, T>(
+            items: &Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
+        where
+            T: Iterator<Item = &'a str>,
+        {
+            match args.next() {
+                Some("jsonl") => match args.next() {
+                    Some("todo") => export_jsonl(items.todos.items()),
+                    Some("task") => export_jsonl(items.tasks.items()),
+                    Some("event") => export_jsonl(items.events.items()),
+                    Some("all") => export_jsonl(
+                        items
+                            .todos
+                            .items()
+                            .into_iter()
+                            .map(|todo| AnyMtcItem::from(todo.clone()))
+                            .chain(
+                                items
+                                    .tasks
+                                    .items()
+                                    .into_iter()
+                                    .map(|task| AnyMtcItem::from(task.clone())),
+                            )
+                            .chain(
+                                items
+                                    .events
+                                    .items()
+                                    .into_iter()
+                                    .map(|event| AnyMtcItem::from(event.clone())),
+                            ),
+                    ),
+                    Some(typ) => Err(CommandError::UnknownType(typ.to_string())),
+                    None => Err(CommandError::MissingArg("No type specified".to_string())),
+                },
+                Some("csv") => {
+                    let stdout = io::stdout();
+                    let mut writer = BufWriter::new(stdout.lock());
+                    match args.next() {
+                        Some("todo") => write_csv(&items.todos, &mut writer),
+                        Some("task") => write_csv(&items.tasks, &mut writer),
+                        Some("event") => write_csv(&items.events, &mut writer),
+                        Some(typ) => return Err(CommandError::UnknownType(typ.to_string())),
+                        None => {
+                            return Err(CommandError::MissingArg("No type specified".to_string()))
+                        }
+                    }
+                    .and_then(|_| writer.flush())
+                    .map_err(|e| CommandError::Io(e.to_string()))
+                }
+                Some("ical") => {
+                    let stdout = io::stdout();
+                    let mut writer = BufWriter::new(stdout.lock());
+                    write_ical(&items.events, &mut writer)
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| CommandError::Io(e.to_string()))
+                }
+                Some("markdown") => {
+                    let week = match args.next() {
+                        Some("week") => true,
+                        Some(arg) => {
+                            return Err(CommandError::Invalid(format!(
+                                "Unknown argument: '{}'",
+                                arg
+                            )))
+                        }
+                        None => false,
+                    };
+                    let stdout = io::stdout();
+                    let mut writer = BufWriter::new(stdout.lock());
+                    write_markdown(items, settings.today(), week, &mut writer)
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| CommandError::Io(e.to_string()))
+                }
+                Some("all") => match args.next() {
+                    Some(dir) => export_all(items, Path::new(dir)),
+                    None => Err(CommandError::MissingArg(
+                        "Missing directory argument.".to_string(),
+                    )),
+                },
+                Some(format) => Err(CommandError::Invalid(format!(
+                    "Unknown export format: '{}'",
+                    format
+                ))),
+                None => Err(CommandError::MissingArg(
+                    "Missing export format argument.".to_string(),
+                )),
+            }
+        }
+
+        pub fn import<'a, T>(items: &mut Items, mut args: T) -> Result<(), CommandError>
+        where
+            T: Iterator<Item = &'a str>,
+        {
+            match args.next() {
+                Some("jsonl") => match args.next() {
+                    Some("todo") => import_jsonl(&mut items.todos),
+                    Some("task") => import_jsonl(&mut items.tasks),
+                    Some("event") => import_jsonl(&mut items.events),
+                    Some("all") => {
+                        let imported: Vec<AnyMtcItem> = read_jsonl()?;
+                        let count = imported.len();
+                        for item in imported {
+                            match item {
+                                AnyMtcItem::Todo(todo) => items.todos.add(todo),
+                                AnyMtcItem::Task(task) => items.tasks.add(task),
+                                AnyMtcItem::Event(event) => items.events.add(event),
+                            };
+                        }
+                        info!("Imported {} items.", count);
+                        Ok(())
+                    }
+                    Some(typ) => Err(CommandError::UnknownType(typ.to_string())),
+                    None => Err(CommandError::MissingArg("No type specified".to_string())),
+                },
+                Some(format) => Err(CommandError::Invalid(format!(
+                    "Unknown import format: '{}'",
+                    format
+                ))),
+                None => Err(CommandError::MissingArg(
+                    "Missing import format argument.".to_string(),
+                )),
+            }
+        }
+
+        /// Writes every item as a single line JSON object to stdout. Items are written one at a time so the whole
+        /// output is never held in memory.
+        fn export_jsonl<I, S>(items: I) -> Result<(), CommandError>
+        where
+            I: IntoIterator<Item = S>,
+            S: Serialize,
+        {
+            let stdout = io::stdout();
+            let mut writer = BufWriter::new(stdout.lock());
+            for item in items {
+                serde_json::to_writer(&mut writer, &item)
+                    .map_err(|e| CommandError::Io(e.to_string()))?;
+                writeln!(writer).map_err(|e| CommandError::Io(e.to_string()))?;
+            }
+            writer.flush().map_err(|e| CommandError::Io(e.to_string()))
+        }
+
+        /// Writes `backup.json` with every list, `calendar.ics` with the events and a CSV file per type to the
+        /// directory. Every file is written from the same items so they always agree with each other.
+        fn export_all(items: &Items, dir: &Path) -> Result<(), CommandError> {
+            let io_err = |e: io::Error| {
+                CommandError::Io(format!(
+                    "Failed to export to '{}'.\nReason: {}",
+                    dir.display(),
+                    e
+                ))
+            };
+            fs::create_dir_all(dir).map_err(io_err)?;
+
+            write_export_file(dir, "backup.json", |w| write_bundle(items, w)).map_err(io_err)?;
+            write_export_file(dir, "calendar.ics", |w| write_ical(&items.events, w))
+                .map_err(io_err)?;
+            write_export_file(dir, "todos.csv", |w| write_csv(&items.todos, w)).map_err(io_err)?;
+            write_export_file(dir, "tasks.csv", |w| write_csv(&items.tasks, w)).map_err(io_err)?;
+            write_export_file(dir, "events.csv", |w| write_csv(&items.events, w))
+                .map_err(io_err)?;
+            Ok(())
+        }
+
+        /// Writes a file to the directory and reports its size.
+        fn write_export_file<F>(dir: &Path, name: &str, write: F) -> io::Result<()>
+        where
+            F: Fn(&mut dyn Write) -> io::Result<()>,
+        {
+            let path = dir.join(name);
+            let mut writer = BufWriter::new(File::create(&path)?);
+            write(&mut writer)?;
+            writer.flush()?;
+            info!(
+                "Wrote '{}' ({} bytes).",
+                path.display(),
+                fs::metadata(&path)?.len()
+            );
+            Ok(())
+        }
+
+        /// Writes all lists as one JSON object with the keys `todos`, `tasks` and `events`.
+        fn write_bundle(items: &Items, writer: &mut dyn Write) -> io::Result<()> {
+            let bundle = serde_json::json!({
+                "todos": items.todos,
+                "tasks": items.tasks,
+                "events": items.events,
+            });
+            serde_json::to_writer_pretty(&mut *writer, &bundle)?;
+            writeln!(writer)
+        }
+
+        /// Writes a header row with the template fields of the type and a row per item.
+        fn write_csv<T: MtcItem + Clone + TemplateFields + Ord>(
+            list: &MtcList<T>,
+            writer: &mut dyn Write,
+        ) -> io::Result<()> {
+            writeln!(writer, "{}", T::FIELDS.join(","))?;
+            let mut items = list.items();
+            items.sort();
+            for item in items {
+                let row: Vec<String> = T::FIELDS
+                    .iter()
+                    .map(|field| csv_field(&item.field(field)))
+                    .collect();
+                writeln!(writer, "{}", row.join(","))?;
+            }
+            Ok(())
+        }
+
+        /// Quotes a CSV field if it contains a separator, a quote or a line break.
+        pub fn csv_field(value: &str) -> String {
+            if value.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            } else {
+                value.to_string()
+            }
+        }
+
+        /// Writes a GitHub flavored Markdown table with a column for every weekday from monday to sunday and a row per
+        /// type. Without `week` the columns are the next date of each weekday like in `show`, with it the dates of the
+        /// current week and the dates are shown in the header.
+        pub fn write_markdown(
+            items: &Items,
+            today: NaiveDate,
+            week: bool,
+            writer: &mut dyn Write,
+        ) -> io::Result<()> {
+            let mut monday = today;
+            while monday.weekday() != Weekday::Mon {
+                monday = monday.pred();
+            }
+            let mut dates = [monday; 7];
+            for (i, date) in dates.iter_mut().enumerate() {
+                *date = monday + chrono::Duration::days(i as i64);
+                // Without `week` every column is the next date of its weekday, today included.
+                if !week && *date < today {
+                    *date += chrono::Duration::days(7);
+                }
+            }
+
+            write!(writer, "|")?;
+            for date in dates.iter() {
+                if week {
+                    write!(writer, " | {} {}", date.weekday(), date)?;
+                } else {
+                    write!(writer, " | {}", date.weekday())?;
+                }
+            }
+            writeln!(writer, " |")?;
+            writeln!(writer, "|---{}|", "|---".repeat(7))?;
+            write_markdown_row(writer, "Todos", &items.todos, &dates)?;
+            write_markdown_row(writer, "Tasks", &items.tasks, &dates)?;
+            write_markdown_row(writer, "Events", &items.events, &dates)
+        }
+
+        fn write_markdown_row<T: MtcItem + Clone + Ord + Display>(
+            writer: &mut dyn Write,
+            name: &str,
+            list: &MtcList<T>,
+            dates: &[NaiveDate; 7],
+        ) -> io::Result<()> {
+            write!(writer, "| {}", name)?;
+            for date in dates.iter() {
+                let mut items_vec = list.items_for_date(*date);
+                items_vec.sort();
+                let cell: Vec<String> = items_vec
+                    .iter()
+                    .map(|item| markdown_text(&format!("{:#}", item)))
+                    .collect();
+                write!(writer, " | {}", cell.join("<br>"))?;
+            }
+            writeln!(writer, " |")
+        }
+
+        /// Escapes the characters that would break a table cell.
+        pub fn markdown_text(value: &str) -> String {
+            value
+                .replace('\\', "\\\\")
+                .replace('|', "\\|")
+                .replace(['\n', '\r'], " ")
+        }
+
+        /// Writes the events as all-day iCalendar events.
+        pub fn write_ical(events: &MtcList<Event>, writer: &mut dyn Write) -> io::Result<()> {
+            let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+            let mut lines = vec![
+                "BEGIN:VCALENDAR".to_string(),
+                "VERSION:2.0".to_string(),
+                "PRODID:-//mtc//mtc//EN".to_string(),
+            ];
+            let mut events = events.items();
+            events.sort();
+            for event in events {
+                lines.push("BEGIN:VEVENT".to_string());
+                lines.push(format!("UID:{}", event.uuid()));
+                lines.push(format!("DTSTAMP:{}", stamp));
+                match event.time() {
+                    // Without a timezone the times are in the local time of whoever reads the calendar.
+                    Some((start, end)) => {
+                        let date = event.date();
+                        let format = "%Y%m%dT%H%M%S";
+                        lines.push(format!("DTSTART:{}", date.and_time(start).format(format)));
+                        lines.push(format!("DTEND:{}", date.and_time(end).format(format)));
+                    }
+                    None => {
+                        lines.push(format!(
+                            "DTSTART;VALUE=DATE:{}",
+                            event.date().format("%Y%m%d")
+                        ));
+                        lines.push(format!(
+                            "DTEND;VALUE=DATE:{}",
+                            event.date().succ().format("%Y%m%d")
+                        ));
+                    }
+                }
+                lines.push(format!("SUMMARY:{}", ical_text(event.body())));
+                if let Some(location) = event.location() {
+                    lines.push(format!("LOCATION:{}", ical_text(location)));
+                }
+                if event.is_yearly() {
+                    lines.push("RRULE:FREQ=YEARLY".to_string());
+                }
+                lines.push("END:VEVENT".to_string());
+            }
+            lines.push("END:VCALENDAR".to_string());
+
+            for line in lines {
+                write!(writer, "{}\r\n", fold_ical_line(&line))?;
+            }
+            Ok(())
+        }
+
+        pub fn ical_text(text: &str) -> String {
+            text.replace('\\', "\\\\")
+                .replace(';', "\\;")
+                .replace(',', "\\,")
+                .replace('\n', "\\n")
+        }
+
+        /// Splits lines longer than 75 bytes into continuation lines starting with a space as iCalendar requires.
+        pub fn fold_ical_line(line: &str) -> String {
+            let mut folded = String::new();
+            let mut len = 0;
+            for c in line.chars() {
+                if len + c.len_utf8() > 75 {
+                    folded.push_str("\r\n ");
+                    len = 1;
+                }
+                folded.push(c);
+                len += c.len_utf8();
+            }
+            folded
+        }
+
+        /// Adds every item read from stdin as JSON lines. Nothing is added if any of the lines is invalid.
+        fn import_jsonl<T: MtcItem + Clone + DeserializeOwned>(
+            list: &mut MtcList<T>,
+        ) -> Result<(), CommandError> {
+            let imported = read_jsonl()?;
+            let count = imported.len();
+            list.extend(imported);
+            info!("Imported {} items.", count);
+            Ok(())
+        }
+
+        /// Reads every line of stdin as a JSON object. Fails without reading the rest if a line is invalid.
+        fn read_jsonl<T: DeserializeOwned>() -> Result<Vec<T>, CommandError> {
+            let stdin = io::stdin();
+            let mut imported = Vec::new();
+            for (i, line) in stdin.lock().lines().enumerate() {
+                let line = line.map_err(|e| CommandError::Io(e.to_string()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let item: T = serde_json::from_str(&line).map_err(|e| {
+                    CommandError::Parse(format!("Cannot parse line {}.\nReason: {}", i + 1, e))
+                })?;
+                imported.push(item);
             }
-            Some(typ) => return Err(format!("Unknown type: '{}'", typ)),
-            None => return Err("No type specified".to_string()),
+            Ok(imported)
         }
-        Ok(())
     }
 
     mod add_cmd {
+        use std::io::BufRead;
+
         use super::readers::*;
         use super::*;
 
-        pub fn add<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        pub fn add<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
             match args.next() {
                 Some("todo") => add_todo(items, args)?,
-                Some("task") => add_task(items, args)?,
-                Some("event") => add_event(items, args)?,
-                Some(typ) => return Err(format!("Unknown type: '{}'", typ)),
-                None => return Err("No type specified".to_string()),
+                Some("quick") => add_quick(items, args)?,
+                Some("task") => add_task(items, settings, args)?,
+                Some("event") => add_event(items, settings, args)?,
+                Some(typ) => return Err(CommandError::UnknownType(typ.to_string())),
+                None => return Err(CommandError::MissingArg("No type specified".to_string())),
             }
             Ok(())
         }
 
-        fn add_todo<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        /// Adds a todo. With `--stdin` instead of a body every non-empty line of stdin is added as a todo with the
+        /// same weekday, interval and context.
+        fn add_todo<'a, T>(items: &mut Items, args: T) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
-            let body = read_body(args.next())?;
-            let weekday = read_weekday(args.next())?;
-            items.todos.add(Todo::new(body, weekday));
+            let mut args = args.peekable();
+            let body = if args.peek() == Some(&"--stdin") {
+                args.next();
+                None
+            } else {
+                Some(read_body(args.next())?)
+            };
+            let mut weekday = None;
+            let mut interval = None;
+            let mut context = None;
+
+            while let Some(arg) = args.next() {
+                match arg {
+                    "--every" => interval = Some(read_interval(args.next())?),
+                    "--context" => context = Some(read_context(args.next())?),
+                    _ => weekday = read_weekday(Some(arg))?,
+                }
+            }
+
+            if weekday.is_some() && interval.is_some() {
+                return Err(CommandError::Invalid(
+                    "A todo cannot have both a weekday and an interval.".to_string(),
+                ));
+            }
+
+            let new_todo = |body: String| {
+                let mut todo = Todo::new(body, weekday);
+                todo.set_interval_days(interval);
+                todo.set_context(context.clone());
+                todo
+            };
+            match body {
+                Some(body) => {
+                    items.todos.add(new_todo(body));
+                }
+                None => {
+                    let stdin = io::stdin();
+                    for line in stdin.lock().lines() {
+                        let line = line.map_err(|e| CommandError::Io(e.to_string()))?;
+                        let body = line.trim();
+                        if body.is_empty() {
+                            continue;
+                        }
+                        let id = items.todos.add(new_todo(body.to_string()));
+                        info!("Added '{}' (ID: {}).", body, id);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Adds a todo to the inbox. All arguments are used as the body so that quick capturing never
+        /// requires quoting or extra arguments.
+        fn add_quick<'a, T>(items: &mut Items, args: T) -> Result<(), CommandError>
+        where
+            T: Iterator<Item = &'a str>,
+        {
+            let body = args.collect::<Vec<&str>>().join(" ");
+            if body.is_empty() {
+                return Err(CommandError::MissingArg(
+                    "Missing item body argument.".to_string(),
+                ));
+            }
+            let mut todo = Todo::new(body, None);
+            todo.set_inbox(true);
+            items.todos.add(todo);
             Ok(())
         }
 
-        fn add_task<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        fn add_task<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
             let body = read_body(args.next())?;
-            let duration = read_duration(args.next())?;
+            let duration = read_duration(args.next(), settings.max_task_duration)?;
             let mut weekdays = [false, false, false, false, false, false, false];
+            let mut context = None;
 
-            for day_str in args {
-                let day = read_weekday(Some(day_str))?;
-                if let Some(weekday) = day {
+            while let Some(arg) = args.next() {
+                if arg == "--context" {
+                    context = Some(read_context(args.next())?);
+                } else if let Some(weekday) = read_weekday(Some(arg))? {
                     weekdays[(weekday.number_from_monday() - 1) as usize] = true;
                 }
             }
 
             let mut task = Task::new(body, duration, None);
             task.set_weekdays(weekdays);
+            task.set_context(context);
             items.tasks.add(task);
             Ok(())
         }
 
-        fn add_event<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        fn add_event<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
             let body = read_body(args.next())?;
-            let date = read_date(args.next())?;
-            items.events.add(Event::new(body, date));
+            let date = read_date(&mut args, settings.today())?;
+            let mut event = Event::new(body, date);
+            while let Some(arg) = args.next() {
+                match arg {
+                    "--at" => event.set_location(Some(read_location(args.next())?)),
+                    "--time" => event.set_time(Some(read_time_window(args.next())?)),
+                    "--yearly" => event.set_yearly(true),
+                    "--since-year" => {
+                        event.set_yearly(true);
+                        event.set_since_year(Some(read_year(args.next())?));
+                    }
+                    _ => {
+                        return Err(CommandError::Invalid(format!(
+                            "Unknown argument: '{}'",
+                            arg
+                        )))
+                    }
+                }
+            }
+            event
+                .validate()
+                .map_err(|errors| CommandError::Invalid(errors.join("\n")))?;
+            items.events.add(event);
             Ok(())
         }
     }
@@ -198,62 +1810,91 @@ mod commands {
         use super::readers::*;
         use super::*;
 
-        pub fn set<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        pub fn set<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
             match args.next() {
                 Some("todo") => set_todo(items, args)?,
-                Some("task") => set_task(items, args)?,
-                Some("event") => set_event(items, args)?,
-                Some(typ) => return Err(format!("Unknown type: '{}'", typ)),
-                None => return Err("No type specified".to_string()),
+                Some("task") => set_task(items, settings, args)?,
+                Some("event") => set_event(items, settings, args)?,
+                Some(typ) => return Err(CommandError::UnknownType(typ.to_string())),
+                None => return Err(CommandError::MissingArg("No type specified".to_string())),
             }
             Ok(())
         }
 
-        fn set_todo<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        fn set_todo<'a, T>(items: &mut Items, mut args: T) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
-            let id = read_id(args.next())?;
+            let id = resolve_id(&items.todos, args.next())?;
             let old = items.todos.get_by_id(id);
             if old.is_none() {
-                return Err("No item with the given id found.".to_string());
+                return Err(CommandError::NotFound(
+                    "No item with the given id found.".to_string(),
+                ));
             }
             let old = old.unwrap();
 
             // This is not optimal but the slight performance overhead is not significant.
             let mut body = old.body().clone();
-            let date = old.date();
-            let new: Todo;
-
-            match args.next() {
-                Some("body") => {
-                    body = read_body(args.next())?;
-                    new = Todo::new_dated(body, date);
-                }
-                Some("weekday") => {
-                    let weekday = read_weekday(args.next())?;
-                    new = Todo::new(body, weekday);
+            let mut weekday = None;
+            let mut context = old.context().map(|c| c.to_string());
+
+            // Every property is read before anything is changed so that an invalid property aborts the whole edit.
+            let mut property = args.next();
+            if property.is_none() {
+                return Err(CommandError::MissingArg(
+                    "Missing property argument.".to_string(),
+                ));
+            }
+            while let Some(prop) = property {
+                match prop {
+                    "body" => body = read_body(args.next())?,
+                    "weekday" => weekday = Some(read_weekday(args.next())?),
+                    // Leaving the context out at the end clears it.
+                    "context" => {
+                        context = args.next().map(|c| read_context(Some(c))).transpose()?
+                    }
+                    _ => return Err(CommandError::Invalid("Unknown property.".to_string())),
                 }
-                Some(_) => return Err("Unknown property.".to_string()),
-                None => return Err("Missing property argument.".to_string()),
-            };
+                property = args.next();
+            }
+
+            // Changing a copy keeps the uuid and the properties that can't be set here.
+            let mut new = old.clone();
+            new.set_body(body);
+            if let Some(weekday) = weekday {
+                // Setting the weekday also clears the interval and takes the todo out of the inbox.
+                new.set_weekday(weekday);
+                new.set_inbox(false);
+            }
+            new.set_context(context);
 
             items.todos.mark_removed(id).unwrap();
             items.todos.add(new);
             Ok(())
         }
 
-        fn set_task<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        fn set_task<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
-            let id = read_id(args.next())?;
+            let id = resolve_id(&items.tasks, args.next())?;
             let old = items.tasks.get_by_id(id);
             if old.is_none() {
-                return Err("No item with the given id found.".to_string());
+                return Err(CommandError::NotFound(
+                    "No item with the given id found.".to_string(),
+                ));
             }
             let old = old.unwrap();
 
@@ -261,62 +1902,88 @@ mod commands {
             let mut body = old.body().clone();
             let mut weekdays = old.weekdays();
             let mut duration = old.duration();
+            let mut context = old.context().map(|c| c.to_string());
+
+            let mut args = args.peekable();
+            let mut property = args.next();
+            if property.is_none() {
+                return Err(CommandError::MissingArg(
+                    "Missing property argument.".to_string(),
+                ));
+            }
+            while let Some(prop) = property {
+                match prop {
+                    "body" => body = read_body(args.next())?,
+                    "duration" => {
+                        duration = read_duration(args.next(), settings.max_task_duration)?
+                    }
+                    "weekday" => {
+                        weekdays = [false, false, false, false, false, false, false];
 
-            match args.next() {
-                Some("body") => {
-                    body = read_body(args.next())?;
-                }
-                Some("duration") => {
-                    duration = read_duration(args.next())?;
-                }
-                Some("weekday") => {
-                    weekdays = [false, false, false, false, false, false, false];
-
-                    for day_str in args {
-                        let day = read_weekday(Some(day_str))?;
-                        if let Some(weekday) = day {
+                        // Every following weekday belongs to this property. Property names are never weekdays.
+                        while let Some(day_str) = args.next_if(|arg| parse_weekday(arg).is_ok()) {
+                            let weekday = parse_weekday(day_str)?;
                             weekdays[(weekday.number_from_monday() - 1) as usize] = true;
                         }
                     }
+                    // Leaving the context out at the end clears it.
+                    "context" => {
+                        context = args.next().map(|c| read_context(Some(c))).transpose()?
+                    }
+                    _ => return Err(CommandError::Invalid("Unknown property.".to_string())),
                 }
-                Some(_) => return Err("Unknown property.".to_string()),
-                None => return Err("Missing property argument.".to_string()),
-            };
+                property = args.next();
+            }
 
-            let mut new = Task::new(body, duration, None);
+            // Changing a copy keeps the uuid and the days the task was done on.
+            let mut new = old.clone();
+            new.set_body(body);
+            new.set_duration(duration);
             new.set_weekdays(weekdays);
+            new.set_context(context);
             items.tasks.mark_removed(id).unwrap();
             items.tasks.add(new);
             Ok(())
         }
 
-        fn set_event<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        fn set_event<'a, T>(
+            items: &mut Items,
+            settings: &Settings,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
-            let id = read_id(args.next())?;
+            let id = resolve_id(&items.events, args.next())?;
             let old = items.events.get_by_id(id);
             if old.is_none() {
-                return Err("No item with the given id found.".to_string());
+                return Err(CommandError::NotFound(
+                    "No item with the given id found.".to_string(),
+                ));
             }
             let old = old.unwrap();
 
-            // This is not optimal but the slight performance overhead is not significant.
-            let mut body = old.body().clone();
-            let mut date = old.date();
+            // Changing a copy keeps the properties that can't be set here, such as the time and whether the event
+            // is yearly.
+            let mut new = old.clone();
 
-            match args.next() {
-                Some("body") => {
-                    body = read_body(args.next())?;
-                }
-                Some("date") => {
-                    date = read_date(args.next())?;
+            let mut property = args.next();
+            if property.is_none() {
+                return Err(CommandError::MissingArg(
+                    "Missing property argument.".to_string(),
+                ));
+            }
+            while let Some(prop) = property {
+                match prop {
+                    "body" => new.set_body(read_body(args.next())?),
+                    "date" => new.set_date(read_date(&mut args, settings.today())?),
+                    // Leaving the location out at the end clears it.
+                    "location" => new.set_location(args.next().map(|l| l.to_string())),
+                    _ => return Err(CommandError::Invalid("Unknown property.".to_string())),
                 }
-                Some(_) => return Err("Unknown property.".to_string()),
-                None => return Err("Missing property argument.".to_string()),
-            };
+                property = args.next();
+            }
 
-            let new = Event::new(body, date);
             items.events.mark_removed(id).unwrap();
             items.events.add(new);
             Ok(())
@@ -326,57 +1993,516 @@ mod commands {
     mod readers {
         use super::*;
 
-        pub fn read_id(next: Option<&str>) -> Result<usize, String> {
+        pub fn read_id(next: Option<&str>) -> Result<usize, CommandError> {
             if let Some(s) = next {
-                return usize::from_str(s)
-                    .map_err(|_| "Invalid input. Input a valid ID.".to_string());
+                return usize::from_str(s).map_err(|_| {
+                    CommandError::Parse("Invalid input. Input a valid ID.".to_string())
+                });
             }
-            Err("No ID specified.".to_string())
+            Err(CommandError::MissingArg("No ID specified.".to_string()))
         }
 
-        pub fn read_weekday(next: Option<&str>) -> Result<Option<Weekday>, String> {
-            if let Some(inp) = next {
-                match Weekday::from_str(inp) {
-                    Ok(day) => Ok(Some(day)),
-                    Err(_) => Err(format!("Cannot parse '{}' to a weekday.", inp)),
+        /// Reads an id or resolves it from a unique part of an item's body if the input is not a number.
+        pub fn resolve_id<T: MtcItem + Clone>(
+            list: &MtcList<T>,
+            next: Option<&str>,
+        ) -> Result<usize, CommandError> {
+            let inp = match next {
+                Some(inp) => inp,
+                None => return read_id(next),
+            };
+            if let Ok(id) = usize::from_str(inp) {
+                return Ok(id);
+            }
+
+            let needle = inp.to_lowercase();
+            let matches: Vec<&T> = list
+                .items()
+                .into_iter()
+                .filter(|item| item.body().to_lowercase().contains(&needle))
+                .collect();
+
+            match matches.len() {
+                0 => Err(CommandError::NotFound(format!(
+                    "No item matching '{}' found.",
+                    inp
+                ))),
+                1 => Ok(matches[0].id()),
+                _ => {
+                    let mut msg = format!("Multiple items match '{}':", inp);
+                    for item in matches {
+                        msg.push_str(&format!("\n\t{} (ID: {})", item.body(), item.id()));
+                    }
+                    Err(CommandError::Invalid(msg))
                 }
+            }
+        }
+
+        pub fn read_weekday(next: Option<&str>) -> Result<Option<Weekday>, CommandError> {
+            if let Some(inp) = next {
+                parse_weekday(inp).map(Some)
             } else {
                 Ok(None)
             }
         }
 
-        pub fn read_duration(next: Option<&str>) -> Result<u32, String> {
+        /// Parses a full weekday name, a three letter abbreviation or a number from 1 (monday) to 7 (sunday).
+        /// Names are case-insensitive.
+        pub fn parse_weekday(inp: &str) -> Result<Weekday, CommandError> {
+            match inp.to_lowercase().as_str() {
+                "monday" | "mon" | "1" => Ok(Weekday::Mon),
+                "tuesday" | "tue" | "2" => Ok(Weekday::Tue),
+                "wednesday" | "wed" | "3" => Ok(Weekday::Wed),
+                "thursday" | "thu" | "4" => Ok(Weekday::Thu),
+                "friday" | "fri" | "5" => Ok(Weekday::Fri),
+                "saturday" | "sat" | "6" => Ok(Weekday::Sat),
+                "sunday" | "sun" | "7" => Ok(Weekday::Sun),
+                _ => Err(CommandError::Parse(format!(
+                    "Cannot parse '{}' to a weekday. Use a name like 'monday', an abbreviation like 'mon' or a number from 1 (monday) to 7 (sunday).",
+                    inp
+                ))),
+            }
+        }
+
+        /// Reads a task duration in minutes from 1 to `max`.
+        pub fn read_duration(next: Option<&str>, max: u32) -> Result<u32, CommandError> {
             if let Some(inp) = next {
                 match u32::from_str(inp) {
-                    Ok(dur) => Ok(dur),
-                    Err(_) => Err(format!("Cannot parse '{}' to a number.", inp)),
+                    Ok(dur) if dur >= 1 && dur <= max => Ok(dur),
+                    Ok(_) => Err(CommandError::Invalid(format!(
+                        "A task duration must be between 1 and {} minutes.",
+                        max
+                    ))),
+                    Err(_) => Err(CommandError::Parse(format!(
+                        "Cannot parse '{}' to a number.",
+                        inp
+                    ))),
                 }
             } else {
-                Err("Missing task duration argument.".to_string())
+                Err(CommandError::MissingArg(
+                    "Missing task duration argument.".to_string(),
+                ))
             }
         }
 
-        pub fn read_body(next: Option<&str>) -> Result<String, String> {
+        pub fn read_interval(next: Option<&str>) -> Result<u32, CommandError> {
             if let Some(inp) = next {
-                Ok(inp.to_string())
+                match u32::from_str(inp) {
+                    Ok(days) if days > 0 => Ok(days),
+                    _ => Err(CommandError::Parse(format!(
+                        "Cannot parse '{}' to a positive number.",
+                        inp
+                    ))),
+                }
             } else {
-                Err("Missing item body argument.".to_string())
+                Err(CommandError::MissingArg(
+                    "Missing interval argument.".to_string(),
+                ))
             }
         }
 
-        pub fn read_date(next: Option<&str>) -> Result<NaiveDate, String> {
+        /// Reads a number of days from 1 to a year.
+        pub fn read_day_count(next: Option<&str>) -> Result<u32, CommandError> {
             if let Some(inp) = next {
-                match NaiveDate::from_str(inp) {
-                    Ok(date) => Ok(date),
-                    Err(_) => Err(format!("Cannot parse '{}' to a date.", inp)),
+                match u32::from_str(inp) {
+                    Ok(days) if (1..=366).contains(&days) => Ok(days),
+                    _ => Err(CommandError::Parse(format!(
+                        "Cannot parse '{}' to a number of days between 1 and 366.",
+                        inp
+                    ))),
                 }
             } else {
-                Err("Missing event date argument.".to_string())
+                Err(CommandError::MissingArg(
+                    "Missing day count argument.".to_string(),
+                ))
+            }
+        }
+
+        pub fn read_body(next: Option<&str>) -> Result<String, CommandError> {
+            if let Some(inp) = next {
+                Ok(inp.to_string())
+            } else {
+                Err(CommandError::MissingArg(
+                    "Missing item body argument.".to_string(),
+                ))
+            }
+        }
+
+        pub fn read_location(next: Option<&str>) -> Result<String, CommandError> {
+            if let Some(inp) = next {
+                Ok(inp.to_string())
+            } else {
+                Err(CommandError::MissingArg(
+                    "Missing event location argument.".to_string(),
+                ))
+            }
+        }
+
+        pub fn read_year(next: Option<&str>) -> Result<i32, CommandError> {
+            let inp =
+                next.ok_or_else(|| CommandError::MissingArg("Missing year argument.".to_string()))?;
+            i32::from_str(inp)
+                .map_err(|_| CommandError::Parse(format!("Cannot parse '{}' to a year.", inp)))
+        }
+
+        /// Reads a context such as `home`. A context is a single word and may start with `@` which is left out.
+        pub fn read_context(next: Option<&str>) -> Result<String, CommandError> {
+            let inp = next
+                .ok_or_else(|| CommandError::MissingArg("Missing context argument.".to_string()))?;
+            let context = inp.strip_prefix('@').unwrap_or(inp);
+            if context.is_empty() || context.contains(char::is_whitespace) {
+                return Err(CommandError::Invalid(format!(
+                    "Invalid context '{}'. A context is a single word.",
+                    inp
+                )));
+            }
+            Ok(context.to_string())
+        }
+
+        /// Reads the start and end times of an event given as `HH:MM-HH:MM`. The start must be before the end.
+        pub fn read_time_window(
+            next: Option<&str>,
+        ) -> Result<(NaiveTime, NaiveTime), CommandError> {
+            let inp =
+                next.ok_or_else(|| CommandError::MissingArg("Missing time argument.".to_string()))?;
+            let invalid = || {
+                CommandError::Invalid(format!(
+                    "Invalid time '{}'. Times are given as HH:MM-HH:MM.",
+                    inp
+                ))
+            };
+            let (start, end) = inp.split_once('-').ok_or_else(invalid)?;
+            let start = NaiveTime::parse_from_str(start, "%H:%M").map_err(|_| invalid())?;
+            let end = NaiveTime::parse_from_str(end, "%H:%M").map_err(|_| invalid())?;
+            if start >= end {
+                return Err(CommandError::Invalid(
+                    "The start time must be before the end time.".to_string(),
+                ));
+            }
+            Ok((start, end))
+        }
+
+        /// Reads a date given as `YYYY-MM-DD` or relative to today as `today`, `tomorrow`, `yesterday`, `+<n>d`,
+        /// `+<n>w` or `next <weekday>`. The weekday after `next` is read from the following argument.
+        pub fn read_date<'a, T>(args: &mut T, today: NaiveDate) -> Result<NaiveDate, CommandError>
+        where
+            T: Iterator<Item = &'a str>,
+        {
+            match args.next() {
+                Some("next") => match args.next() {
+                    Some(weekday) => parse_date(&format!("next {}", weekday), today),
+                    None => Err(CommandError::MissingArg(
+                        "Missing weekday argument for 'next'.".to_string(),
+                    )),
+                },
+                Some(inp) => parse_date(inp, today),
+                None => Err(CommandError::MissingArg(
+                    "Missing event date argument.".to_string(),
+                )),
+            }
+        }
+
+        /// Parses a date in any of the forms accepted by `read_date` relative to `today`. `next <weekday>` is always
+        /// after today so on a friday `next friday` is a week later.
+        pub fn parse_date(inp: &str, today: NaiveDate) -> Result<NaiveDate, CommandError> {
+            let date = match inp {
+                "today" => Some(today),
+                "tomorrow" => Some(today.succ()),
+                "yesterday" => Some(today.pred()),
+                _ => {
+                    if let Some(weekday) = inp.strip_prefix("next ") {
+                        let weekday = parse_weekday(weekday)?;
+                        let mut date = today.succ();
+                        while date.weekday() != weekday {
+                            date = date.succ();
+                        }
+                        Some(date)
+                    } else if let Some(offset) = inp.strip_prefix('+') {
+                        let (count, days_per_unit) = if let Some(count) = offset.strip_suffix('d') {
+                            (count, 1)
+                        } else if let Some(count) = offset.strip_suffix('w') {
+                            (count, 7)
+                        } else {
+                            (offset, 0)
+                        };
+                        u32::from_str(count)
+                            .ok()
+                            .filter(|_| days_per_unit > 0)
+                            .and_then(|count| {
+                                today.checked_add_signed(chrono::Duration::days(
+                                    count as i64 * days_per_unit,
+                                ))
+                            })
+                    } else {
+                        NaiveDate::from_str(inp).ok()
+                    }
+                }
+            };
+
+            date.ok_or_else(|| {
+                CommandError::Parse(format!(
+                    "Cannot parse '{}' to a date. Use YYYY-MM-DD, today, tomorrow, yesterday, +<n>d, +<n>w or next <weekday>.",
+                    inp
+                ))
+            })
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            // A wednesday.
+            fn today() -> NaiveDate {
+                NaiveDate::from_ymd(2022, 6, 15)
+            }
+
+            #[test]
+            fn read_time_window_accepts_ordered_times() {
+                assert_eq!(
+                    read_time_window(Some("09:00-10:30")).unwrap(),
+                    (NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(10, 30, 0))
+                );
+                assert!(read_time_window(Some("10:30-09:00")).is_err());
+                assert!(read_time_window(Some("09:00")).is_err());
+            }
+
+            #[test]
+            fn parse_date_accepts_iso_dates() {
+                assert_eq!(
+                    parse_date("2022-01-05", today()),
+                    Ok(NaiveDate::from_ymd(2022, 1, 5))
+                );
+            }
+
+            #[test]
+            fn parse_date_accepts_day_names() {
+                assert_eq!(parse_date("today", today()), Ok(today()));
+                assert_eq!(
+                    parse_date("tomorrow", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 16))
+                );
+                assert_eq!(
+                    parse_date("yesterday", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 14))
+                );
+            }
+
+            #[test]
+            fn parse_date_accepts_offsets() {
+                assert_eq!(parse_date("+0d", today()), Ok(today()));
+                assert_eq!(
+                    parse_date("+3d", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 18))
+                );
+                assert_eq!(
+                    parse_date("+2w", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 29))
+                );
+            }
+
+            #[test]
+            fn parse_date_next_weekday_is_after_today() {
+                assert_eq!(
+                    parse_date("next thursday", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 16))
+                );
+                assert_eq!(
+                    parse_date("next mon", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 20))
+                );
+                // The same weekday as today is a week later.
+                assert_eq!(
+                    parse_date("next wednesday", today()),
+                    Ok(NaiveDate::from_ymd(2022, 6, 22))
+                );
+            }
+
+            #[test]
+            fn parse_date_rejects_invalid_input() {
+                for inp in [
+                    "",
+                    "2022-13-01",
+                    "+3",
+                    "+d",
+                    "-3d",
+                    "+3m",
+                    "next",
+                    "next week",
+                ] {
+                    assert!(parse_date(inp, today()).is_err(), "{}", inp);
+                }
+            }
+        }
+    }
+
+    mod template {
+        use super::*;
+
+        /// Fields of an item that can be used as `{placeholder}`s in a `--format` template.
+        pub trait TemplateFields {
+            const NAME: &'static str;
+            const FIELDS: &'static [&'static str];
+
+            /// Returns the value of one of `FIELDS`.
+            fn field(&self, name: &str) -> String;
+        }
+
+        impl TemplateFields for Todo {
+            const NAME: &'static str = "todos";
+            const FIELDS: &'static [&'static str] =
+                &["id", "body", "date", "weekday", "context", "uuid"];
+
+            fn field(&self, name: &str) -> String {
+                match name {
+                    "id" => self.id().to_string(),
+                    "body" => self.body().clone(),
+                    "date" => self.date().to_string(),
+                    "weekday" => self.date().weekday().to_string(),
+                    "context" => self.context().unwrap_or("").to_string(),
+                    "uuid" => self.uuid().to_string(),
+                    _ => String::new(),
+                }
+            }
+        }
+
+        impl TemplateFields for Task {
+            const NAME: &'static str = "tasks";
+            const FIELDS: &'static [&'static str] =
+                &["id", "body", "duration", "weekdays", "context", "uuid"];
+
+            fn field(&self, name: &str) -> String {
+                match name {
+                    "id" => self.id().to_string(),
+                    "body" => self.body().clone(),
+                    "duration" => self.duration().to_string(),
+                    "weekdays" => {
+                        let mut days = Vec::new();
+                        let mut day = Weekday::Mon;
+                        for is_for in self.weekdays() {
+                            if is_for {
+                                days.push(day.to_string());
+                            }
+                            day = day.succ();
+                        }
+                        days.join(",")
+                    }
+                    "context" => self.context().unwrap_or("").to_string(),
+                    "uuid" => self.uuid().to_string(),
+                    _ => String::new(),
+                }
+            }
+        }
+
+        impl TemplateFields for Event {
+            const NAME: &'static str = "events";
+            const FIELDS: &'static [&'static str] =
+                &["id", "body", "date", "weekday", "location", "uuid"];
+
+            fn field(&self, name: &str) -> String {
+                match name {
+                    "id" => self.id().to_string(),
+                    "body" => self.body().clone(),
+                    "date" => self.date().to_string(),
+                    "weekday" => self.date().weekday().to_string(),
+                    "location" => self.location().unwrap_or("").to_string(),
+                    "uuid" => self.uuid().to_string(),
+                    _ => String::new(),
+                }
+            }
+        }
+
+        enum Part {
+            Text(String),
+            Field(String),
+        }
+
+        /// A parsed `--format` template such as `{id}\t{body}`. `\t`, `\n` and `\\` are replaced with a tab,
+        /// a newline and a backslash so that they can be given without shell quoting tricks.
+        pub struct Template {
+            parts: Vec<Part>,
+        }
+
+        impl Template {
+            pub fn parse(template: &str) -> Result<Template, CommandError> {
+                let mut parts = Vec::new();
+                let mut text = String::new();
+                let mut chars = template.chars();
+
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some('t') => text.push('\t'),
+                            Some('n') => text.push('\n'),
+                            Some(other) => {
+                                text.push('\\');
+                                text.push(other);
+                            }
+                            None => text.push('\\'),
+                        },
+                        '{' => {
+                            let mut name = String::new();
+                            let mut closed = false;
+                            for c in chars.by_ref() {
+                                if c == '}' {
+                                    closed = true;
+                                    break;
+                                }
+                                name.push(c);
+                            }
+                            if !closed {
+                                return Err(CommandError::Parse(format!(
+                                    "Unclosed placeholder '{{{}' in the format.",
+                                    name
+                                )));
+                            }
+                            parts.push(Part::Text(std::mem::take(&mut text)));
+                            parts.push(Part::Field(name));
+                        }
+                        _ => text.push(c),
+                    }
+                }
+                parts.push(Part::Text(text));
+
+                Ok(Template { parts })
+            }
+
+            /// Returns an error listing the valid placeholders if the template uses a placeholder `T` doesn't have.
+            pub fn check<T: TemplateFields>(&self) -> Result<(), CommandError> {
+                for part in self.parts.iter() {
+                    if let Part::Field(name) = part {
+                        if !T::FIELDS.contains(&name.as_str()) {
+                            return Err(CommandError::Parse(format!(
+                                "Unknown placeholder '{{{}}}' for {}. Valid placeholders are: {}",
+                                name,
+                                T::NAME,
+                                T::FIELDS
+                                    .iter()
+                                    .map(|f| format!("{{{}}}", f))
+                                    .collect::<Vec<String>>()
+                                    .join(", ")
+                            )));
+                        }
+                    }
+                }
+                Ok(())
+            }
+
+            pub fn render<T: TemplateFields>(&self, item: &T) -> String {
+                let mut line = String::new();
+                for part in self.parts.iter() {
+                    match part {
+                        Part::Text(text) => line.push_str(text),
+                        Part::Field(name) => line.push_str(&item.field(name)),
+                    }
+                }
+                line
             }
         }
     }
 
     mod show_cmd {
+        use super::readers::{parse_weekday, read_date, read_day_count};
+        use super::template::{Template, TemplateFields};
         use super::*;
 
         const WEEKDAYS: &[Weekday] = &[
@@ -389,143 +2515,572 @@ mod commands {
             Weekday::Sun,
         ];
 
-        pub fn show<'a, T>(items: &Items, mut args: T) -> Result<(), String>
+        pub fn show<'a, T>(items: &Items, settings: &Settings, args: T) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
+            let mut args: Vec<&str> = args.collect();
+            let filtered;
+            let items = if let Some(i) = args.iter().position(|arg| *arg == "--context") {
+                if i + 1 >= args.len() {
+                    return Err(CommandError::MissingArg(
+                        "Missing context argument for '--context'.".to_string(),
+                    ));
+                }
+                let context = readers::read_context(Some(args.remove(i + 1)))?;
+                args.remove(i);
+                filtered = with_context(items, &context);
+                &filtered
+            } else {
+                items
+            };
+            if let Some(i) = args.iter().position(|arg| *arg == "--format") {
+                if i + 1 >= args.len() {
+                    return Err(CommandError::MissingArg(
+                        "Missing template argument for '--format'.".to_string(),
+                    ));
+                }
+                let template = Template::parse(args.remove(i + 1))?;
+                args.remove(i);
+                return show_formatted(items, settings, &args, &template);
+            }
+            let mut args = args.into_iter();
+
             match args.next() {
-                Some("todos") => show_all_todos(items),
-                Some("tasks") => show_all_tasks(items),
-                Some("events") => show_all_events(items),
-                Some("today") => show_today(items),
-                Some("tomorrow") => show_tomorrow(items),
-                Some("ov") => show_overview(items),
-                Some("overview") => show_overview(items),
-                Some("week") => show_week(items),
-                Some("month") => show_month(items),
-                Some(weekday) => {
-                    if let Ok(wd) = weekday.parse::<Weekday>() {
-                        show_weekday(items, wd);
+                Some("todos") => show_all_todos(items, settings),
+                Some("tasks") => show_all_tasks(items, settings),
+                Some("events") => {
+                    let mut args: Vec<&str> = args.collect();
+                    let show_overdue = match args.iter().position(|arg| *arg == "--show-overdue") {
+                        Some(i) => {
+                            args.remove(i);
+                            true
+                        }
+                        None => false,
+                    };
+                    let (since, until) = read_date_range(args.into_iter(), settings.today())?;
+                    show_all_events(items, settings, since, until, show_overdue);
+                }
+                Some("today") => {
+                    if args.any(|arg| arg == "--agenda") {
+                        show_agenda(items, settings, settings.today());
                     } else {
-                        return Err(format!("Cannot parse '{}' to a weekday.", weekday));
+                        show_today(items, settings);
+                    }
+                }
+                Some("now") => show_now(items, settings, settings.now()),
+                Some("tomorrow") => show_tomorrow(items, settings),
+                Some("ov") => show_overview(items, settings),
+                Some("overview") => show_overview(items, settings),
+                Some("week") => {
+                    let from_today = args.any(|arg| arg == "--from-today");
+                    show_week(items, settings, from_today);
+                }
+                Some("month") => show_month(items, settings),
+                Some("next") => {
+                    let days = read_day_count(args.next())?;
+                    let hide_empty = args.any(|arg| arg == "--hide-empty");
+                    show_next(items, settings, days, hide_empty);
+                }
+                Some("duplicates") => show_duplicates(items),
+                Some("inbox") => show_inbox(items, settings),
+                Some("expired") => show_expired(items, settings),
+                Some("pending") => show_pending(items, settings),
+                Some(weekday) => show_weekday(items, settings, parse_weekday(weekday)?),
+                None => show_all(items, settings),
+            }
+            Ok(())
+        }
+
+        /// Returns a copy of the items with only the todos and tasks of the context. Events have no context so none are
+        /// left. The other items are only marked removed so that the ids stay the same.
+        fn with_context(items: &Items, context: &str) -> Items {
+            let mut filtered = items.clone();
+            filtered
+                .todos
+                .mark_removed_where(|todo| todo.context() != Some(context));
+            filtered
+                .tasks
+                .mark_removed_where(|task| task.context() != Some(context));
+            filtered.events.mark_removed_where(|_| true);
+            filtered
+        }
+
+        /// Prints one line per item using the template instead of the usual grouped output.
+        fn show_formatted(
+            items: &Items,
+            settings: &Settings,
+            args: &[&str],
+            template: &Template,
+        ) -> Result<(), CommandError> {
+            let today = settings.today();
+            let for_date = |date: NaiveDate| {
+                (
+                    Some(items.todos.items_for_date(date)),
+                    Some(items.tasks.items_for_date(date)),
+                    Some(items.events.items_for_date(date)),
+                )
+            };
+
+            let (todos, tasks, events) = match args.first().copied() {
+                None => (
+                    Some(items.todos.items()),
+                    Some(items.tasks.items()),
+                    Some(items.events.items()),
+                ),
+                Some("todos") => (Some(items.todos.items()), None, None),
+                Some("tasks") => (None, Some(items.tasks.items()), None),
+                Some("events") => {
+                    let (since, until) =
+                        read_date_range(args[1..].iter().copied(), settings.today())?;
+                    let events = items
+                        .events
+                        .items()
+                        .into_iter()
+                        .filter(|e| in_date_range(e, since, until))
+                        .collect();
+                    (None, None, Some(events))
+                }
+                Some("inbox") => {
+                    let todos = items
+                        .todos
+                        .items()
+                        .into_iter()
+                        .filter(|todo| todo.is_inbox())
+                        .collect();
+                    (Some(todos), None, None)
+                }
+                Some("today") => for_date(today),
+                Some("tomorrow") => for_date(today.succ()),
+                Some(arg @ ("ov" | "overview" | "week" | "month" | "next" | "duplicates")) => {
+                    return Err(CommandError::Invalid(format!(
+                        "'--format' cannot be used with 'show {}'.",
+                        arg
+                    )))
+                }
+                Some(weekday) => {
+                    let weekday = parse_weekday(weekday)?;
+                    let mut date = today;
+                    while date.weekday() != weekday {
+                        date = date.succ();
                     }
+                    for_date(date)
+                }
+            };
+
+            // Check every template first so that an invalid placeholder doesn't leave a partial output.
+            if todos.is_some() {
+                template.check::<Todo>()?;
+            }
+            if tasks.is_some() {
+                template.check::<Task>()?;
+            }
+            if events.is_some() {
+                template.check::<Event>()?;
+            }
+
+            print_formatted(template, events);
+            print_formatted(template, tasks);
+            print_formatted(template, todos);
+            Ok(())
+        }
+
+        fn print_formatted<T: TemplateFields + Ord>(template: &Template, items: Option<Vec<&T>>) {
+            if let Some(mut items) = items {
+                items.sort();
+                for item in items {
+                    println!("{}", template.render(item));
+                }
+            }
+        }
+
+        fn show_all(items: &Items, settings: &Settings) {
+            for wd in WEEKDAYS.iter() {
+                println!("{}", settings.weekday_name(*wd));
+                println!("\tTodos: ");
+                show_list_weekday(
+                    &items.todos,
+                    *wd,
+                    settings.today(),
+                    &settings.prefixes.todos,
+                );
+
+                println!("\tTasks: ");
+                show_list_weekday(
+                    &items.tasks,
+                    *wd,
+                    settings.today(),
+                    &settings.prefixes.tasks,
+                );
+            }
+            show_all_events(items, settings, None, None, false);
+        }
+
+        fn show_weekday(items: &Items, settings: &Settings, weekday: Weekday) {
+            let mut date = settings.today();
+            while date.weekday() != weekday {
+                date = date.succ();
+            }
+            show_all_date(items, settings, date);
+        }
+
+        fn show_today(items: &Items, settings: &Settings) {
+            show_all_date(items, settings, settings.today());
+        }
+
+        /// Shows the events happening at the instant and today's tasks and todos. Tasks, todos and events without a
+        /// time are for the whole day so they are always shown.
+        fn show_now(items: &Items, settings: &Settings, now: NaiveDateTime) {
+            println!(
+                "{} {} {}:",
+                settings.weekday_name(now.weekday()),
+                now.date(),
+                now.format("%H:%M")
+            );
+            println!("\tEvents: ");
+            show_list_datetime(&items.events, now, &settings.prefixes.events);
+
+            println!("\tTasks: ");
+            show_list_datetime(&items.tasks, now, &settings.prefixes.tasks);
+
+            println!("\tTodos: ");
+            show_list_datetime(&items.todos, now, &settings.prefixes.todos);
+        }
+
+        fn show_list_datetime<T: MtcItem + Clone + Ord + Display>(
+            list: &MtcList<T>,
+            datetime: NaiveDateTime,
+            prefix: &str,
+        ) {
+            let mut items_vec: Vec<&T> = list
+                .items()
+                .into_iter()
+                .filter(|item| item.for_datetime(datetime))
+                .collect();
+            items_vec.sort();
+            show_list(&items_vec, datetime.date(), prefix);
+        }
+
+        fn show_tomorrow(items: &Items, settings: &Settings) {
+            show_all_date(items, settings, settings.today().succ());
+        }
+
+        fn show_overview(items: &Items, settings: &Settings) {
+            let mut day = settings.today();
+            for _ in 0..4 {
+                show_all_date(items, settings, day);
+                day = day.succ();
+            }
+        }
+
+        fn show_week(items: &Items, settings: &Settings, from_today: bool) {
+            let mut day = settings.today();
+
+            // Step back to the start of the current calendar week unless a rolling week is wanted.
+            if !from_today {
+                while day.weekday() != settings.week_start {
+                    day = day.pred();
+                }
+            }
+
+            for _ in 0..7 {
+                show_all_date(items, settings, day);
+                day = day.succ();
+            }
+        }
+
+        fn show_month(items: &Items, settings: &Settings) {
+            let mut day = settings.today();
+
+            for _ in 0..30 {
+                show_all_date(items, settings, day);
+                day = day.succ();
+            }
+        }
+
+        fn show_next(items: &Items, settings: &Settings, days: u32, hide_empty: bool) {
+            let mut day = settings.today();
+
+            for _ in 0..days {
+                let view = items.items_for_date(day);
+                // Tasks repeat weekly so they don't make a day worth showing.
+                let is_empty = view.events.is_empty() && view.todos.is_empty();
+                if !(hide_empty && is_empty) {
+                    show_all_date(items, settings, day);
                 }
-                None => show_all(items),
+                day = day.succ();
             }
-            Ok(())
         }
 
-        fn show_all(items: &Items) {
+        fn show_all_todos(items: &Items, settings: &Settings) {
             for wd in WEEKDAYS.iter() {
-                println!("{}", wd);
+                println!("{}", settings.weekday_name(*wd));
                 println!("\tTodos: ");
-                show_list_weekday(&items.todos, *wd);
+                show_list_weekday(
+                    &items.todos,
+                    *wd,
+                    settings.today(),
+                    &settings.prefixes.todos,
+                );
+            }
+        }
 
+        fn show_all_tasks(items: &Items, settings: &Settings) {
+            for wd in WEEKDAYS.iter() {
+                println!("{}", settings.weekday_name(*wd));
                 println!("\tTasks: ");
-                show_list_weekday(&items.tasks, *wd);
+                show_list_weekday(
+                    &items.tasks,
+                    *wd,
+                    settings.today(),
+                    &settings.prefixes.tasks,
+                );
             }
-            show_all_events(&items);
         }
 
-        fn show_weekday(items: &Items, weekday: Weekday) {
-            let mut date = Local::today();
-            while date.weekday() != weekday {
-                date = date.succ();
-            }
-            show_all_date(&items, date.naive_local());
+        /// Returns true if the event is between the optional dates. A yearly event is compared by its first
+        /// occurrence since `since` so that it isn't hidden by the year it was added on.
+        pub fn in_date_range(
+            event: &Event,
+            since: Option<NaiveDate>,
+            until: Option<NaiveDate>,
+        ) -> bool {
+            let date = since
+                .and_then(|since| event.next_occurrence(since))
+                .unwrap_or_else(|| event.date());
+            since.map_or(true, |since| date >= since) && until.map_or(true, |until| date <= until)
         }
 
-        fn show_today(items: &Items) {
-            let day = Local::today();
-            show_all_date(items, day.naive_local());
+        fn read_date_range<'a, T>(
+            mut args: T,
+            today: NaiveDate,
+        ) -> Result<(Option<NaiveDate>, Option<NaiveDate>), CommandError>
+        where
+            T: Iterator<Item = &'a str>,
+        {
+            let mut since = None;
+            let mut until = None;
+            while let Some(arg) = args.next() {
+                match arg {
+                    "--since" => since = Some(read_date(&mut args, today)?),
+                    "--until" => until = Some(read_date(&mut args, today)?),
+                    _ => {
+                        return Err(CommandError::Invalid(format!(
+                            "Unknown argument: '{}'",
+                            arg
+                        )))
+                    }
+                }
+            }
+            Ok((since, until))
         }
 
-        fn show_tomorrow(items: &Items) {
-            let day = Local::today().succ();
-            show_all_date(items, day.naive_local());
+        fn show_all_events(
+            items: &Items,
+            settings: &Settings,
+            since: Option<NaiveDate>,
+            until: Option<NaiveDate>,
+            show_overdue: bool,
+        ) {
+            let mut events_vec = items.events.items();
+            events_vec.retain(|event| in_date_range(event, since, until));
+            events_vec.sort();
+            if show_overdue {
+                // Overdue events are shown first so that they aren't missed among the others.
+                let overdue: Vec<&&Event> = events_vec.iter().filter(|e| e.is_overdue()).collect();
+                if !overdue.is_empty() {
+                    println!("Overdue: ");
+                    for i in overdue {
+                        println!("\t{}{}", with_space(&settings.prefixes.events), i);
+                    }
+                }
+                events_vec.retain(|event| !event.is_overdue());
+            }
+            println!("Events: ");
+            for i in events_vec.iter() {
+                println!("\t{}{}", with_space(&settings.prefixes.events), i);
+            }
         }
 
-        fn show_overview(items: &Items) {
-            let mut day = Local::today().naive_local();
-            for _ in 0..4 {
-                show_all_date(items, day);
-                day = day.succ();
+        /// Shows the items the next sync will remove.
+        fn show_expired(items: &Items, settings: &Settings) {
+            if settings.expire_todos {
+                println!("Todos: ");
+                for todo in items.todos.expired_items() {
+                    println!("\t{}{}", with_space(&settings.prefixes.todos), todo);
+                }
+            }
+
+            println!("Events: ");
+            for event in items.events.expired_items() {
+                println!("\t{}{}", with_space(&settings.prefixes.events), event);
             }
         }
 
-        fn show_week(items: &Items) {
-            let mut day = Local::today().naive_local();
-            let orig_day = day.weekday();
+        /// Shows the changes the next sync sends to the server.
+        fn show_pending(items: &Items, settings: &Settings) {
+            println!("Todos: ");
+            show_list_pending(&items.todos, settings, &settings.prefixes.todos);
 
-            while {
-                show_all_date(items, day);
-                day = day.succ();
+            println!("Tasks: ");
+            show_list_pending(&items.tasks, settings, &settings.prefixes.tasks);
 
-                day.weekday() != orig_day
-            } {}
+            println!("Events: ");
+            show_list_pending(&items.events, settings, &settings.prefixes.events);
         }
 
-        fn show_month(items: &Items) {
-            let mut day = Local::today().naive_local();
-
-            for _ in 0..30 {
-                show_all_date(items, day);
-                day = day.succ();
+        fn show_list_pending<T: MtcItem + Clone + Display>(
+            list: &MtcList<T>,
+            settings: &Settings,
+            prefix: &str,
+        ) {
+            if let Some(time) = list.last_synced() {
+                println!(
+                    "\tLast synced {}",
+                    settings.local_time(time).format("%Y-%m-%d %H:%M")
+                );
+            }
+            let (new, removed) = list.pending();
+            for item in new {
+                println!("\t+ {}{:#}", with_space(prefix), item);
+            }
+            for item in removed {
+                println!("\t- {}{:#}", with_space(prefix), item);
             }
         }
 
-        fn show_all_todos(items: &Items) {
-            for wd in WEEKDAYS.iter() {
-                println!("{}", wd);
-                println!("\tTodos: ");
-                show_list_weekday(&items.todos, *wd);
+        fn show_inbox(items: &Items, settings: &Settings) {
+            println!("Inbox: ");
+            let mut todos_vec: Vec<&Todo> = items
+                .todos
+                .items()
+                .into_iter()
+                .filter(|todo| todo.is_inbox())
+                .collect();
+            todos_vec.sort();
+            for i in todos_vec.iter() {
+                println!("\t{}{}", with_space(&settings.prefixes.todos), i);
             }
         }
 
-        fn show_all_tasks(items: &Items) {
-            for wd in WEEKDAYS.iter() {
-                println!("{}", wd);
-                println!("\tTasks: ");
-                show_list_weekday(&items.tasks, *wd);
+        fn show_duplicates(items: &Items) {
+            println!("Todos: ");
+            show_list_duplicates(&items.todos);
+
+            println!("Tasks: ");
+            show_list_duplicates(&items.tasks);
+
+            println!("Events: ");
+            show_list_duplicates(&items.events);
+        }
+
+        fn show_list_duplicates<T: MtcItem + Clone>(list: &MtcList<T>) {
+            let mut shown: Vec<&str> = Vec::new();
+            for item in list.items() {
+                let body = item.body();
+                if shown.contains(&body) {
+                    continue;
+                }
+                shown.push(body);
+
+                let duplicates = list.get_all_by_body(body);
+                if duplicates.len() > 1 {
+                    let ids: Vec<String> = duplicates.iter().map(|i| i.id().to_string()).collect();
+                    println!("\t{} (IDs: {})", body, ids.join(", "));
+                }
             }
         }
 
-        fn show_all_events(items: &Items) {
-            println!("Events: ");
-            let mut events_vec = items.events.items();
-            events_vec.sort();
-            for i in events_vec.iter() {
-                println!("\t{}", i);
+        /// Shows all items for a date in one list. Items don't have times so the list is ordered by
+        /// type, events first since they are bound to the date.
+        fn show_agenda(items: &Items, settings: &Settings, date: NaiveDate) {
+            println!("{} {}:", settings.weekday_name(date.weekday()), date);
+            show_agenda_list(&items.events, date, "event", &settings.prefixes.events);
+            show_agenda_list(&items.tasks, date, "task", &settings.prefixes.tasks);
+            show_agenda_list(&items.todos, date, "todo", &settings.prefixes.todos);
+        }
+
+        fn show_agenda_list<T: MtcItem + Clone + Ord + Display>(
+            list: &MtcList<T>,
+            date: NaiveDate,
+            typ: &str,
+            prefix: &str,
+        ) {
+            let mut items_vec = list.items_for_date(date);
+            items_vec.sort();
+            for i in items_vec.iter() {
+                println!(
+                    "\t[{}] {}{}{}",
+                    typ,
+                    done_mark(*i, date),
+                    with_space(prefix),
+                    i.occurrence_on(date)
+                );
             }
         }
 
-        fn show_all_date(items: &Items, date: NaiveDate) {
-            println!("{} {}:", date.weekday(), date);
+        fn show_all_date(items: &Items, settings: &Settings, date: NaiveDate) {
+            println!("{} {}:", settings.weekday_name(date.weekday()), date);
             println!("\tEvents: ");
-            show_list_date(&items.events, date);
+            show_list_date(&items.events, date, &settings.prefixes.events);
 
             println!("\tTodos: ");
-            show_list_date(&items.todos, date);
+            show_list_date(&items.todos, date, &settings.prefixes.todos);
         }
 
-        fn show_list_date<T: MtcItem + Clone + Ord + Display>(list: &MtcList<T>, date: NaiveDate) {
+        fn show_list_date<T: MtcItem + Clone + Ord + Display>(
+            list: &MtcList<T>,
+            date: NaiveDate,
+            prefix: &str,
+        ) {
             let mut items_vec = list.items_for_date(date);
             items_vec.sort();
-            show_list(&items_vec);
+            show_list(&items_vec, date, prefix);
         }
 
+        // Uses the next date of the weekday so events are only shown if they are on that date. The
+        // `show <weekday>` path uses the same semantics through `show_all_date`.
         fn show_list_weekday<T: MtcItem + Clone + Ord + Display>(
             list: &MtcList<T>,
             weekday: Weekday,
+            today: NaiveDate,
+            prefix: &str,
         ) {
             let mut items_vec = list.items_for_weekday(weekday);
             items_vec.sort();
-            show_list(&items_vec);
+            let mut date = today;
+            while date.weekday() != weekday {
+                date = date.succ();
+            }
+            show_list(&items_vec, date, prefix);
         }
 
-        fn show_list<T: Display + MtcItem>(list: &[&T]) {
+        /// Shows the items of a date, marking the ones done on it.
+        fn show_list<T: Display + MtcItem + Clone>(list: &[&T], date: NaiveDate, prefix: &str) {
             for i in list.iter() {
-                println!("\t\t{}", i);
+                println!(
+                    "\t\t{}{}{}",
+                    done_mark(*i, date),
+                    with_space(prefix),
+                    i.occurrence_on(date)
+                );
+            }
+        }
+
+        fn done_mark<T: MtcItem>(item: &T, date: NaiveDate) -> &'static str {
+            if item.is_done_on(date) {
+                "[done] "
+            } else {
+                ""
+            }
+        }
+
+        /// Returns the configured type prefix followed by a space or nothing if there is no prefix.
+        fn with_space(prefix: &str) -> String {
+            if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{} ", prefix)
             }
         }
     }
@@ -538,137 +3093,795 @@ mod commands {
 
         use super::*;
 
+        /// The item types to sync.
+        struct Selection {
+            todos: bool,
+            tasks: bool,
+            events: bool,
+        }
+
         #[derive(Serialize, Deserialize)]
         struct Config {
             username: String,
             address: String,
             server_path: String,
+            /// Appends a line about every sync to `history.log` in the server path.
+            #[serde(default)]
+            history: bool,
+            /// Prepended to the names of the lists on the server, such as `alice-` for `alice-todos.json`, so that
+            /// users can share a server path.
+            #[serde(default)]
+            file_prefix: String,
         }
 
-        pub fn sync<'a, T>(items: &mut Items, mut args: T) -> Result<(), String>
+        pub fn sync<'a, T>(
+            items: &mut Items,
+            snapshots: &mut SyncSnapshots,
+            settings: &Settings,
+            options: &GlobalOptions,
+            dir: &Path,
+            mut args: T,
+        ) -> Result<(), CommandError>
         where
             T: Iterator<Item = &'a str>,
         {
-            // Only events can expire
-            items.events.remove_expired();
-
             let mut overwrite = false;
-            match args.next() {
-                None => {}
-                Some("overwrite") => overwrite = true,
-                Some("self") => {
+            let mut sync_self = false;
+            let mut only: Option<Selection> = None;
+            let mut config_path: Option<PathBuf> = None;
+            while let Some(arg) = args.next() {
+                match arg {
+                    "overwrite" => overwrite = true,
+                    "--config" => match args.next() {
+                        Some(path) => config_path = Some(PathBuf::from(path)),
+                        None => {
+                            return Err(CommandError::MissingArg(
+                                "Missing path argument for '--config'.".to_string(),
+                            ))
+                        }
+                    },
+                    "self" => sync_self = true,
+                    "--only" => {
+                        let selection = only.get_or_insert(Selection {
+                            todos: false,
+                            tasks: false,
+                            events: false,
+                        });
+                        match args.next() {
+                            Some("todos" | "todo") => selection.todos = true,
+                            Some("tasks" | "task") => selection.tasks = true,
+                            Some("events" | "event") => selection.events = true,
+                            Some(typ) => return Err(CommandError::UnknownType(typ.to_string())),
+                            None => {
+                                return Err(CommandError::MissingArg(
+                                    "Missing type argument for '--only'.".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(CommandError::Invalid("Unknown command.".to_string()));
+                    }
+                }
+            }
+            let only = only.unwrap_or(Selection {
+                todos: true,
+                tasks: true,
+                events: true,
+            });
+
+            // Tasks never expire and todos only expire if the user has opted in.
+            if only.events {
+                if settings.archive_events {
+                    // Drain a copy so that the events are kept if archiving them fails.
+                    let mut events = items.events.clone();
+                    let expired = events.drain_expired();
+                    archive_items(&dir.join("events-archive.json"), expired).map_err(|e| {
+                        CommandError::Io(format!(
+                            "Failed to archive expired events.\nReason: {}",
+                            e
+                        ))
+                    })?;
+                    items.events = events;
+                } else {
+                    items.events.remove_expired();
+                }
+            }
+            if only.todos && settings.expire_todos {
+                items.todos.remove_expired();
+            }
+
+            if sync_self {
+                // The client lists no longer match the server lists after this so they must be synced fully.
+                if only.todos {
+                    snapshots.todos = None;
                     items.todos.sync_self();
+                }
+                if only.tasks {
+                    snapshots.tasks = None;
                     items.tasks.sync_self();
-                    items.events.sync_self();
-                    return Ok(());
                 }
-                _ => {
-                    return Err("Unknown command.".to_string());
+                if only.events {
+                    snapshots.events = None;
+                    items.events.sync_self();
                 }
+                return Ok(());
             }
 
-            let config = read_config()?;
-            if let Err(e) = connect(items, &config, overwrite) {
-                return Err(format!("Sync failed.\nReason: {}", e));
+            let config = read_config(config_path.as_deref())?;
+            if let Err(e) = connect(items, snapshots, &config, overwrite, &only, options.verbose) {
+                if let SyncError::NotFound(_) = e {
+                    return Err(CommandError::Sync(format!(
+                        "Sync failed.\nReason: {}\nRun 'mtc sync overwrite' to upload your items the first time.",
+                        e
+                    )));
+                }
+                return Err(CommandError::Sync(format!("Sync failed.\nReason: {}", e)));
             }
-
             Ok(())
         }
 
-        fn connect(items: &mut Items, conf: &Config, overwrite: bool) -> Result<(), Error> {
-            let tcp = TcpStream::connect(&conf.address)?;
-            let mut sess = Session::new()?;
+        fn connect(
+            items: &mut Items,
+            snapshots: &mut SyncSnapshots,
+            conf: &Config,
+            overwrite: bool,
+            only: &Selection,
+            verbose: bool,
+        ) -> Result<(), SyncError> {
+            // Logged to stderr so that the steps never mix with the output of the command.
+            let mut log = |msg: &str| {
+                if verbose {
+                    eprintln!("{}", msg);
+                }
+            };
+
+            log(&format!("Connecting to {}.", conf.address));
+            let connect_error = |e: Error| SyncError::Connect(e);
+            let tcp = TcpStream::connect(&conf.address).map_err(connect_error)?;
+            let mut sess = Session::new().map_err(|e| connect_error(e.into()))?;
             sess.set_tcp_stream(tcp);
-            sess.handshake()?;
+            sess.handshake().map_err(|e| connect_error(e.into()))?;
+
+            // Reading the password from the environment lets syncs run unattended, for example from cron.
+            let pass = match env::var("MTC_PASSWORD") {
+                Ok(pass) => pass,
+                Err(_) => rpassword::prompt_password_stdout(&format!(
+                    "{}@{}'s password: ",
+                    conf.username, conf.address
+                ))
+                .map_err(connect_error)?,
+            };
+            log(&format!("Authenticating as {}.", conf.username));
+            sess.userauth_password(&conf.username, &pass)
+                .map_err(|e| connect_error(e.into()))?;
 
-            let pass = rpassword::prompt_password_stdout(&format!(
-                "{}@{}'s password: ",
-                conf.username, conf.address
-            ))?;
-            sess.userauth_password(&conf.username, &pass)?;
+            // Counted before syncing since the sync clears the states.
+            let history = history_line(items, conf, overwrite, only);
 
-            sync_remote(
-                &sess,
-                &mut items.todos,
-                &Path::new(&conf.server_path).join(Path::new("todos.json")),
-                overwrite,
-            )?;
-            sync_remote(
-                &sess,
-                &mut items.tasks,
-                &Path::new(&conf.server_path).join(Path::new("tasks.json")),
-                overwrite,
-            )?;
-            sync_remote(
-                &sess,
-                &mut items.events,
-                &Path::new(&conf.server_path).join(Path::new("events.json")),
-                overwrite,
-            )?;
+            sync_selected(items, snapshots, &sess, conf, overwrite, only, &mut log)?;
+
+            if conf.history {
+                let path = Path::new(&conf.server_path).join("history.log");
+                log(&format!("Appending to {}.", path.display()));
+                append_server_log(&sess, &path, &history)?;
+            }
+            log("Done.");
+            Ok(())
+        }
+
+        /// Returns a line for the history log with the time, the user and the changes sent by each synced type.
+        fn history_line(items: &Items, conf: &Config, overwrite: bool, only: &Selection) -> String {
+            let mut line = format!(
+                "{}\t{}",
+                Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+                conf.username
+            );
+            if overwrite {
+                line.push_str("\toverwrite");
+            }
+            let mut push_counts = |name: &str, selected: bool, (new, _, removed)| {
+                if selected {
+                    line.push_str(&format!("\t{} +{} -{}", name, new, removed));
+                }
+            };
+            push_counts("todos", only.todos, items.todos.state_counts());
+            push_counts("tasks", only.tasks, items.tasks.state_counts());
+            push_counts("events", only.events, items.events.state_counts());
+            line
+        }
+
+        fn sync_selected(
+            items: &mut Items,
+            snapshots: &mut SyncSnapshots,
+            sess: &Session,
+            conf: &Config,
+            overwrite: bool,
+            only: &Selection,
+            log: &mut dyn FnMut(&str),
+        ) -> Result<(), SyncError> {
+            let files = ServerFiles::with_prefix(Path::new(&conf.server_path), &conf.file_prefix);
+            if only.todos && only.tasks && only.events {
+                let [todos, tasks, events] = sync_remote_all_incremental(
+                    sess,
+                    &mut items.todos,
+                    &mut items.tasks,
+                    &mut items.events,
+                    &files,
+                    SyncOptions {
+                        overwrite,
+                        snapshots: &mut *snapshots,
+                        log: &mut *log,
+                    },
+                )?;
+                log_report(log, "todos", &todos);
+                log_report(log, "tasks", &tasks);
+                log_report(log, "events", &events);
+                return Ok(());
+            }
 
+            // scp gives no file attributes for a snapshot so the next full sync transfers these lists.
+            if only.todos {
+                snapshots.todos = None;
+                log(&format!("Syncing {} over scp.", files.todos.display()));
+                let report = sync_remote(sess, &mut items.todos, &files.todos, overwrite)?;
+                log_report(log, "todos", &report);
+            }
+            if only.tasks {
+                snapshots.tasks = None;
+                log(&format!("Syncing {} over scp.", files.tasks.display()));
+                let report = sync_remote(sess, &mut items.tasks, &files.tasks, overwrite)?;
+                log_report(log, "tasks", &report);
+            }
+            if only.events {
+                snapshots.events = None;
+                log(&format!("Syncing {} over scp.", files.events.display()));
+                let report = sync_remote(sess, &mut items.events, &files.events, overwrite)?;
+                log_report(log, "events", &report);
+            }
             Ok(())
         }
 
-        fn read_config() -> Result<Config, String> {
+        fn log_report(log: &mut dyn FnMut(&str), name: &str, report: &SyncReport) {
+            log(&format!(
+                "{}: sent +{} -{}, {} items.",
+                name, report.sent_new, report.sent_removed, report.items
+            ));
+        }
+
+        /// Reads the sync config from `explicit` if given and from `sync.json` in the mtc config directory otherwise.
+        fn read_config(explicit: Option<&Path>) -> Result<Config, CommandError> {
+            let path = match explicit {
+                Some(path) => {
+                    if !path.exists() {
+                        return Err(CommandError::Sync(format!(
+                            "Config file '{}' doesn't exist.",
+                            path.display()
+                        )));
+                    }
+                    path.to_path_buf()
+                }
+                None => default_config_path()?,
+            };
+
+            let file = File::open(path).map_err(|e| {
+                CommandError::Sync(format!("Failed to open config file.\nReason: {}", e))
+            })?;
+            let reader = BufReader::new(file);
+
+            serde_json::from_reader(reader).map_err(|e| {
+                CommandError::Sync(format!("Failed to parse config file.\nReason: {}", e))
+            })
+        }
+
+        /// Explains how to set up syncing when there is no config yet, which usually means that this is the first sync.
+        fn first_run_guide(path: &Path) -> String {
+            format!(
+                "No sync config found. To set up syncing create '{}' with the following fields:\n\n\
+                 {{\n    \"username\": \"<ssh user>\",\n    \"address\": \"<host>:22\",\n    \"server_path\": \"<directory on the server>\"\n}}\n\n\
+                 'history' can be set to true to keep a log of syncs on the server and 'file_prefix' to a prefix such as \"alice-\" \
+                 for the list file names when sharing the server directory. The server directory must exist.\n\
+                 Then run 'mtc sync overwrite' once to upload your items. Later syncs only need 'mtc sync'.",
+                path.display()
+            )
+        }
+
+        fn default_config_path() -> Result<PathBuf, CommandError> {
             if let Some(cnf) = dirs::config_dir() {
                 let dir = cnf.join("mtc/");
                 if let Err(e) = fs::create_dir_all(&dir) {
-                    return Err(format!("Failed to create config directory.\nReason: {}", e));
+                    return Err(CommandError::Sync(format!(
+                        "Failed to create config directory.\nReason: {}",
+                        e
+                    )));
                 }
                 let path = dir.join("sync.json");
                 if !path.exists() {
-                    return Err("No config file found. Please create one.".to_string());
+                    return Err(CommandError::Sync(first_run_guide(&path)));
                 }
-
-                let file = File::open(path)
-                    .map_err(|e| format!("Failed to open config file.\nReason: {}", e))?;
-                let reader = BufReader::new(file);
-
-                serde_json::from_reader(reader)
-                    .map_err(|e| format!("Failed to parse config file.\nReason: {}", e))
+                Ok(path)
             } else {
-                Err("Cannot locate a config directory. Your os may not be supported.".to_string())
+                Err(CommandError::Sync(
+                    "Cannot locate a config directory. Your os may not be supported.".to_string(),
+                ))
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn weekday_name(weekday: Weekday) -> String {
+            weekday.to_string().to_lowercase()
+        }
+
+        #[test]
+        fn remove_todos_by_weekday_leaves_todos_not_for_the_weekday() {
+            let today = Local::today().naive_local();
+            let mut list = MtcList::new(false);
+            list.add(Todo::new("Today".to_string(), Some(today.weekday())));
+            list.add(Todo::new_dated(
+                "Overdue".to_string(),
+                today - chrono::Duration::days(4),
+            ));
+            list.add(Todo::new("Any day".to_string(), None));
+
+            let weekday = weekday_name(today.weekday());
+            remove_from(
+                &mut list,
+                vec!["--weekday", weekday.as_str()].into_iter(),
+                todo_for_weekday,
+            )
+            .unwrap();
+
+            let bodies: Vec<&str> = list.items().iter().map(|t| t.body().as_str()).collect();
+            assert_eq!(bodies, vec!["Overdue", "Any day"]);
+        }
+
+        #[test]
+        fn date_range_compares_yearly_events_by_their_next_occurrence() {
+            let date = |y, m, d| NaiveDate::from_ymd(y, m, d);
+            let mut birthday = Event::new("Birthday".to_string(), date(2020, 5, 10));
+            birthday.set_yearly(true);
+            let one_off = Event::new("Meeting".to_string(), date(2020, 5, 10));
+
+            let (since, until) = (Some(date(2026, 1, 1)), Some(date(2026, 6, 1)));
+            assert!(show_cmd::in_date_range(&birthday, since, until));
+            assert!(!show_cmd::in_date_range(&one_off, since, until));
+            assert!(!show_cmd::in_date_range(
+                &birthday,
+                since,
+                Some(date(2026, 5, 1))
+            ));
+            assert!(show_cmd::in_date_range(&one_off, None, until));
+        }
+
+        #[test]
+        fn move_up_moves_the_item_past_its_neighbour() {
+            let mut list = MtcList::new(false);
+            list.add(Todo::new("A".to_string(), None));
+            list.add(Todo::new("B".to_string(), None));
+            list.add(Todo::new("C".to_string(), None));
+
+            move_in(
+                &mut list,
+                vec!["2", "up"].into_iter(),
+                Todo::order,
+                Todo::set_order,
+                |_, _| true,
+            )
+            .unwrap();
+
+            let mut todos = list.items();
+            todos.sort();
+            let bodies: Vec<&str> = todos.iter().map(|t| t.body().as_str()).collect();
+            assert_eq!(bodies, vec!["A", "C", "B"]);
+            // Only the swapped items are readded so that only they are synced.
+            let (new, _, removed) = list.state_counts();
+            assert_eq!((new, removed), (3, 2));
+        }
+
+        #[test]
+        fn ical_export_has_the_times_of_timed_events() {
+            let date = NaiveDate::from_ymd(2026, 3, 5);
+            let mut events = MtcList::new(false);
+            events.add(Event::new("All day".to_string(), date));
+            events.add(
+                EventBuilder::new("Meeting".to_string(), date)
+                    .time(NaiveTime::from_hms(9, 30, 0), NaiveTime::from_hms(10, 0, 0))
+                    .build(),
+            );
+
+            let mut output = Vec::new();
+            export_cmd::write_ical(&events, &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+
+            assert!(output.contains("DTSTART;VALUE=DATE:20260305\r\nDTEND;VALUE=DATE:20260306\r\n"));
+            assert!(output.contains("DTSTART:20260305T093000\r\nDTEND:20260305T100000\r\n"));
+        }
+
+        #[test]
+        fn csv_fields_are_quoted_only_when_needed() {
+            assert_eq!(export_cmd::csv_field("Buy milk"), "Buy milk");
+            assert_eq!(export_cmd::csv_field("Milk, eggs"), "\"Milk, eggs\"");
+            assert_eq!(export_cmd::csv_field("Say \"hi\""), "\"Say \"\"hi\"\"\"");
+            assert_eq!(export_cmd::csv_field("Two\nlines"), "\"Two\nlines\"");
+        }
+
+        #[test]
+        fn ical_text_escapes_special_characters() {
+            assert_eq!(export_cmd::ical_text("a,b;c\\d\ne"), r"a\,b\;c\\d\ne");
+        }
+
+        #[test]
+        fn ical_lines_are_folded_between_characters() {
+            let short = "a".repeat(75);
+            assert_eq!(export_cmd::fold_ical_line(&short), short);
+
+            // The two byte 'ä' doesn't fit after 74 bytes so it starts the next line.
+            let line = format!("{}ä{}", "a".repeat(74), "ö".repeat(40));
+            let folded = export_cmd::fold_ical_line(&line);
+            assert!(folded.starts_with(&format!("{}\r\n ä", "a".repeat(74))));
+            assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+            assert_eq!(folded.replace("\r\n ", ""), line);
+        }
+
+        #[test]
+        fn markdown_text_escapes_table_syntax() {
+            assert_eq!(export_cmd::markdown_text("a|b\\c\nd\re"), r"a\|b\\c d e");
+        }
+
+        #[test]
+        fn markdown_export_leaves_days_without_items_empty() {
+            // A wednesday.
+            let today = NaiveDate::from_ymd(2022, 6, 15);
+            let mut items = Items::new(false);
+            items
+                .events
+                .add(Event::new("Dentist".to_string(), today.succ()));
+
+            let mut output = Vec::new();
+            export_cmd::write_markdown(&items, today, true, &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+
+            assert!(output.contains("| Todos |  |  |  |  |  |  |  |\n"));
+            assert!(output.contains("| Events |  |  |  | 2022-06-16 Thu: Dentist |  |  |  |\n"));
+        }
+
+        #[test]
+        fn remove_todos_by_weekday_matches_todos_saved_before_scheduling_was_tracked() {
+            let date = Local::today().naive_local() + chrono::Duration::days(2);
+            let json = format!(
+                r#"{{"items":[{{"date":"{}","body":"Old","state":"Neutral","id":0}}],"is_server":false}}"#,
+                date
+            );
+            let mut list: MtcList<Todo> = serde_json::from_str(&json).unwrap();
+
+            let weekday = weekday_name(date.weekday());
+            remove_from(
+                &mut list,
+                vec!["--weekday", weekday.as_str()].into_iter(),
+                todo_for_weekday,
+            )
+            .unwrap();
+
+            assert!(list.items().is_empty());
+        }
+
+        #[test]
+        fn reschedule_moves_todos_and_tasks_of_the_weekday() {
+            let today = Local::today().naive_local();
+            let from = today.weekday();
+            let to = from.succ();
+            let mut items = Items::new(false);
+            items.todos.add(Todo::new("Today".to_string(), Some(from)));
+            items.todos.add(Todo::new_dated(
+                "Overdue".to_string(),
+                today - chrono::Duration::days(4),
+            ));
+            items.todos.add(Todo::new("Any day".to_string(), None));
+            items
+                .tasks
+                .add(Task::new("Exercise".to_string(), 30, Some(from)));
+            items.tasks.add(Task::new("Read".to_string(), 30, None));
+
+            let (from_name, to_name) = (weekday_name(from), weekday_name(to));
+            reschedule(
+                &mut items,
+                vec![from_name.as_str(), to_name.as_str()].into_iter(),
+            )
+            .unwrap();
+
+            let todo = items.todos.find(|t| t.body() == "Today").unwrap();
+            assert_eq!(todo.weekday(), Some(to));
+            assert_eq!(todo.state(), ItemState::New);
+            let overdue = items.todos.find(|t| t.body() == "Overdue").unwrap();
+            assert_eq!(overdue.date(), today - chrono::Duration::days(4));
+            let any_day = items.todos.find(|t| t.body() == "Any day").unwrap();
+            assert!(!any_day.is_scheduled());
+            assert_eq!(any_day.date(), today);
+
+            let exercise = items.tasks.find(|t| t.body() == "Exercise").unwrap();
+            assert!(!exercise.is_for_weekday(from));
+            assert!(exercise.is_for_weekday(to));
+            // A task for every day stays for every day.
+            let read = items.tasks.find(|t| t.body() == "Read").unwrap();
+            assert_eq!(read.weekdays(), [false; 7]);
+        }
+
+        #[test]
+        fn reschedule_only_moves_the_given_type() {
+            let from = Local::today().weekday();
+            let to = from.succ();
+            let mut items = Items::new(false);
+            items.todos.add(Todo::new("Shop".to_string(), Some(from)));
+            items
+                .tasks
+                .add(Task::new("Exercise".to_string(), 30, Some(from)));
+
+            let (from_name, to_name) = (weekday_name(from), weekday_name(to));
+            let args = vec![from_name.as_str(), to_name.as_str(), "--type", "task"];
+            reschedule(&mut items, args.into_iter()).unwrap();
+
+            assert_eq!(items.todos.items()[0].weekday(), Some(from));
+            assert!(items.tasks.items()[0].is_for_weekday(to));
+            assert!(reschedule(&mut items, vec!["mon", "mon"].into_iter()).is_err());
+            assert!(reschedule(
+                &mut items,
+                vec!["mon", "tue", "--type", "event"].into_iter()
+            )
+            .is_err());
+        }
+
+        #[test]
+        fn strip_escapes_removes_colors_only() {
+            assert_eq!(strip_escapes("\x1B[1;31m✔\x1B[0m todo"), "✔ todo");
+            assert_eq!(strip_escapes("\x1B7plain"), "plain");
+            assert_eq!(strip_escapes("📅"), "📅");
+        }
+
+        #[test]
+        fn last_color_option_is_used() {
+            let mut args: Vec<String> = vec!["--color", "always", "show", "--no-color"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+            let options = GlobalOptions::parse(&mut args).unwrap();
+            assert_eq!(options.color, ColorChoice::Never);
+            assert!(!options.use_color());
+            assert_eq!(args, vec!["show"]);
+
+            let mut args = vec!["--color".to_string(), "sometimes".to_string()];
+            assert!(GlobalOptions::parse(&mut args).is_err());
+        }
+
+        #[test]
+        fn data_lock_is_exclusive_and_ignores_a_left_behind_file() {
+            let dir = env::temp_dir().join(format!("mtc-lock-test-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            // Like a file left behind by a killed process.
+            fs::write(dir.join(".lock"), "1234").unwrap();
+
+            let lock = DataLock::acquire(&dir).unwrap();
+            assert!(DataLock::acquire(&dir).is_err());
+            drop(lock);
+            let lock = DataLock::acquire(&dir).unwrap();
+            drop(lock);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn toml_lists_keep_every_field() {
+            let dir = env::temp_dir().join(format!("mtc-toml-test-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let settings = Settings {
+                data_format: DataFormat::Toml,
+                ..Settings::default()
+            };
+
+            let date = NaiveDate::from_ymd(2022, 6, 15);
+            let mut items = Items::new(false);
+            items.todos.add(
+                TodoBuilder::new("Water plants".to_string())
+                    .date(date)
+                    .interval_days(3)
+                    .order(2)
+                    .inbox()
+                    .context("home".to_string())
+                    .build(),
+            );
+            items
+                .todos
+                .add(Todo::new("Call mom".to_string(), Some(Weekday::Sun)));
+            let mut task = TaskBuilder::new("Exercise".to_string(), 45)
+                .weekdays([true, false, true, false, true, false, false])
+                .order(-1)
+                .context("gym".to_string())
+                .build();
+            task.mark_done(date);
+            items.tasks.add(task);
+            items.events.add(
+                EventBuilder::new("Meeting".to_string(), date)
+                    .order(1)
+                    .location("Office".to_string())
+                    .time(NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(10, 30, 0))
+                    .since_year(1990)
+                    .build(),
+            );
+            // A removed item keeps the state enum from being only the default.
+            items
+                .todos
+                .mark_removed_where(|todo| todo.body() == "Call mom");
+
+            write_items(&dir, items.clone(), &settings).unwrap();
+            assert!(dir.join("todos.toml").exists());
+            let read = read_items(&dir, settings.data_format).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+
+            // The items don't implement `PartialEq` so their JSON is compared instead.
+            assert_eq!(
+                serde_json::to_value(&read.todos).unwrap(),
+                serde_json::to_value(&items.todos).unwrap()
+            );
+            assert_eq!(
+                serde_json::to_value(&read.tasks).unwrap(),
+                serde_json::to_value(&items.tasks).unwrap()
+            );
+            assert_eq!(
+                serde_json::to_value(&read.events).unwrap(),
+                serde_json::to_value(&items.events).unwrap()
+            );
+        }
+    }
 }
 
 fn main() {
-    if let Some(dir) = dirs::data_dir() {
-        let dir = dir.join(Path::new("mtc/"));
+    // Exiting skips destructors so everything, including the data lock, is dropped in `run` before this.
+    std::process::exit(run());
+}
+
+/// Runs mtc and returns the exit code. See `commands::exit_code` for the codes of failed commands.
+fn run() -> i32 {
+    // There probably is a better way to do this. However the overhead is insignificant so it doesn't matter that much.
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match GlobalOptions::parse(&mut args) {
+        Ok(o) => o,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            println!("Use: 'mtc help' for help.");
+            return commands::EXIT_USAGE;
+        }
+    };
+    QUIET.store(options.quiet, Ordering::Relaxed);
+
+    if let Some(dir) = options.data_dir() {
         if let Err(msg) = fs::create_dir_all(&dir) {
             eprintln!("Failed to create missing directories.");
             eprintln!("{}", msg);
-            return;
+            return 1;
+        }
+
+        let mut settings = match read_settings() {
+            Ok(s) => s,
+            Err(msg) => {
+                eprintln!("Reading settings failed.");
+                eprintln!("{}", msg);
+                return 1;
+            }
+        };
+
+        // Prefixes are the only configurable output so hiding colors only has to strip them.
+        if !options.use_color() {
+            settings.prefixes.strip_escapes();
+        }
+
+        if let Some(tz) = &settings.timezone {
+            if tz.parse::<chrono_tz::Tz>().is_err() {
+                eprintln!("Unknown timezone '{}' in the settings.", tz);
+                return 1;
+            }
         }
 
-        let modified_items = match read_items(&dir) {
-            Ok(i) => commands::handle_command(i),
+        // Watching only reads the items and never writes them so it takes the lock by itself for each redraw.
+        if args.first().map(String::as_str) == Some("show") && args.iter().any(|a| a == "--watch") {
+            return commands::watch(&settings, &dir, &args[1..]);
+        }
+        // The timer of 'do' and the answers of 'triage' can take long so they take the lock by themselves as well.
+        match args.first().map(String::as_str) {
+            Some("do") => return commands::do_unlocked(&settings, &dir, &args[1..]),
+            Some("triage") => return commands::triage_unlocked(&settings, &dir),
+            _ => {}
+        }
+
+        // Held until the items are written so that concurrent invocations don't overwrite each other's changes.
+        let _lock = match DataLock::acquire(&dir) {
+            Ok(lock) => lock,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                return 1;
+            }
+        };
+
+        let snapshots_path = dir.join(Path::new("sync-snapshots.json"));
+        let mut snapshots = read_snapshots(&snapshots_path);
+        let (modified_items, code) = match read_items(&dir, settings.data_format) {
+            Ok(i) => commands::handle_command(i, &mut snapshots, &settings, &options, &dir, &args),
             Err(msg) => {
                 eprintln!("Reading saved items failed.");
                 eprintln!("{}", msg);
-                return;
+                return 1;
             }
         };
 
-        if let Err(msg) = write_items(&dir, modified_items) {
+        if let Err(msg) = write_items(&dir, modified_items, &settings)
+            .and_then(|_| write_snapshots(&snapshots_path, &snapshots))
+        {
             eprintln!("Writing items failed.");
             eprintln!("{}", msg);
+            return 1;
         }
+        code
     } else {
         eprintln!("Cannot locate a data directory. Your os may not be supported.");
-        return;
+        1
+    }
+}
+
+/// An exclusive lock on a file in the data directory which is released when dropped. The lock is an advisory lock of
+/// the operating system so it is released even if the process is killed and the file is left behind.
+struct DataLock {
+    file: File,
+}
+
+impl DataLock {
+    /// Locks the lock file, waiting a moment if another process holds it.
+    fn acquire(dir: &Path) -> Result<DataLock, String> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(".lock"))
+            .map_err(|e| format!("Failed to create a lock file.\nReason: {}", e))?;
+        for _ in 0..20 {
+            if file.try_lock_exclusive().is_ok() {
+                return Ok(DataLock { file });
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Err("Another mtc process is running.".to_string())
     }
 }
 
-fn read_items(dir: &Path) -> Result<Items, String> {
-    let todo_file = dir.join(Path::new("todos.json"));
-    let task_file = dir.join(Path::new("tasks.json"));
-    let event_file = dir.join(Path::new("events.json"));
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        // The file is kept since removing it would let another process lock a new file while one still holds this.
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+fn read_settings() -> Result<Settings, String> {
+    let path = match dirs::config_dir() {
+        Some(cnf) => cnf.join("mtc/config.json"),
+        // Settings are optional so a missing config directory only means the defaults are used.
+        None => return Ok(Settings::default()),
+    };
+
+    if path.exists() {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let reader = BufReader::new(file);
+
+        serde_json::from_reader(reader).map_err(|e| e.to_string())
+    } else {
+        Ok(Settings::default())
+    }
+}
 
-    let todos = read_item(&todo_file)?;
-    let tasks = read_item(&task_file)?;
-    let events = read_item(&event_file)?;
+fn read_items(dir: &Path, format: DataFormat) -> Result<Items, String> {
+    let todos = read_item(&saved_list_path(dir, "todos", format))?;
+    let tasks = read_item(&saved_list_path(dir, "tasks", format))?;
+    let events = read_item(&saved_list_path(dir, "events", format))?;
 
     Ok(Items {
         todos,
@@ -677,36 +3890,138 @@ fn read_items(dir: &Path) -> Result<Items, String> {
     })
 }
 
+/// Returns the path of a list saved in the format.
+fn list_path(dir: &Path, name: &str, format: DataFormat) -> PathBuf {
+    dir.join(format!("{}.{}", name, format.extension()))
+}
+
+/// Returns the path of a saved list. A list only saved in the other format is read from there so that changing the
+/// format keeps the items.
+fn saved_list_path(dir: &Path, name: &str, format: DataFormat) -> PathBuf {
+    let path = list_path(dir, name, format);
+    let other = list_path(dir, name, format.other());
+    if !path.exists() && other.exists() {
+        other
+    } else {
+        path
+    }
+}
+
+/// Returns true if the file at the path is saved as TOML instead of JSON.
+fn is_toml(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "toml")
+}
+
+/// Reads the snapshots of the last sync. A missing or unreadable file only means that the next sync transfers
+/// every list.
+fn read_snapshots(path: &Path) -> SyncSnapshots {
+    File::open(path)
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+        .unwrap_or_default()
+}
+
 fn read_item<T: MtcItem + Clone + DeserializeOwned>(path: &Path) -> Result<MtcList<T>, String> {
     if path.exists() {
-        let file = File::open(path).map_err(|e| e.to_string())?;
-        let reader = BufReader::new(file);
+        let parsed = if is_toml(path) {
+            let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            let file = File::open(path).map_err(|e| e.to_string())?;
+            serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())
+        };
 
-        serde_json::from_reader(reader).map_err(|e| e.to_string())
+        match parsed {
+            Ok(list) => Ok(list),
+            Err(e) => {
+                // A corrupt file shouldn't lock the user out of the app so it is moved aside and an
+                // empty list is used instead.
+                let mut corrupt_path = path.as_os_str().to_owned();
+                corrupt_path.push(".corrupt");
+                fs::rename(path, &corrupt_path).map_err(|e| e.to_string())?;
+
+                eprintln!("Failed to parse '{}'.", path.display());
+                eprintln!("Reason: {}", e);
+                eprintln!(
+                    "The file was moved to '{}' and an empty list is used instead.",
+                    Path::new(&corrupt_path).display()
+                );
+                Ok(MtcList::new(false))
+            }
+        }
     } else {
         Ok(MtcList::new(false))
     }
 }
 
-fn write_items(dir: &Path, items: Items) -> Result<(), String> {
-    let todo_file = dir.join(Path::new("todos.json"));
-    let task_file = dir.join(Path::new("tasks.json"));
-    let event_file = dir.join(Path::new("events.json"));
-
-    write_item(items.todos, &todo_file)?;
-    write_item(items.tasks, &task_file)?;
-    write_item(items.events, &event_file)?;
+fn write_items(dir: &Path, items: Items, settings: &Settings) -> Result<(), String> {
+    let format = settings.data_format;
+    let compact = settings.compact_json;
+    write_item(items.todos, &list_path(dir, "todos", format), compact)?;
+    write_item(items.tasks, &list_path(dir, "tasks", format), compact)?;
+    write_item(items.events, &list_path(dir, "events", format), compact)?;
+
+    // The lists in the other format were read only if these didn't exist and are now outdated.
+    for name in ["todos", "tasks", "events"] {
+        let other = list_path(dir, name, format.other());
+        if other.exists() {
+            fs::remove_file(other).map_err(|e| e.to_string())?;
+        }
+    }
 
     Ok(())
 }
 
+/// Saves the snapshots of the last sync. Only written after the items are saved so that the snapshots never claim
+/// a sync whose items failed to save.
+fn write_snapshots(path: &Path, snapshots: &SyncSnapshots) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    serde_json::to_writer(BufWriter::new(file), snapshots).map_err(|e| e.to_string())
+}
+
+/// Saves the list as it is. Sorting would change the ids, so only the lists on the server are saved sorted.
 fn write_item<T: MtcItem + Clone + DeserializeOwned + Serialize>(
     item: MtcList<T>,
     path: &Path,
+    compact: bool,
+) -> Result<(), String> {
+    if is_toml(path) {
+        // TOML requires plain values before tables which a value reorders.
+        let value = toml::Value::try_from(&item).map_err(|e| e.to_string())?;
+        return fs::write(path, value.to_string()).map_err(|e| e.to_string());
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut writer = BufWriter::new(file);
+    if compact {
+        serde_json::to_writer(&mut writer, &item).map_err(|e| e.to_string())?;
+    } else {
+        serde_json::to_writer_pretty(&mut writer, &item).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Appends items to a plain JSON array in the file, creating the file if it doesn't exist.
+fn archive_items<T: Serialize + DeserializeOwned>(
+    path: &Path,
+    items: Vec<T>,
 ) -> Result<(), String> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let mut archived: Vec<T> = if path.exists() {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    archived.extend(items);
+
     let file = File::create(path).map_err(|e| e.to_string())?;
     let mut writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(&mut writer, &item).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut writer, &archived).map_err(|e| e.to_string())?;
     writer.flush().map_err(|e| e.to_string())?;
     Ok(())
 }
@@ -1,57 +1,544 @@
 use crate::*;
-use serde::{de::DeserializeOwned, Serialize};
-use ssh2::Session;
-use std::io::{Error, Read, Write};
-use std::path::Path;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use ssh2::{ErrorCode, FileStat, OpenFlags, OpenType, RenameFlags, Session, Sftp};
+use std::fmt::Display;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The largest list in bytes [`sync_remote`] uploads with scp. Larger lists should be synced with [`sync_remote_all`]
+/// which uses SFTP.
+pub const DEFAULT_SCP_SIZE_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// An error from syncing with a server.
+#[derive(Debug)]
+pub enum SyncError {
+    /// Opening a channel to the server failed.
+    Connect(Error),
+    /// The server has no list at the path yet. The first sync should be done with `overwrite`.
+    NotFound(PathBuf),
+    /// Downloading or uploading a list failed or didn't transfer the whole list.
+    Transfer(Error),
+    /// The server list isn't valid JSON for the list type.
+    Parse(serde_json::Error),
+    /// The lists can't be merged, for example because the client list is a server list.
+    Merge(String),
+}
+
+impl Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Connect(e) => write!(f, "Connecting to the server failed: {}", e),
+            SyncError::NotFound(path) => write!(
+                f,
+                "The server has no list at '{}' yet. Sync with overwrite the first time to create it.",
+                path.display()
+            ),
+            SyncError::Transfer(e) => write!(f, "Transferring a list failed: {}", e),
+            SyncError::Parse(e) => write!(f, "The server list is invalid: {}", e),
+            SyncError::Merge(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SyncError::Connect(e) | SyncError::Transfer(e) => Some(e),
+            SyncError::Parse(e) => Some(e),
+            SyncError::NotFound(_) | SyncError::Merge(_) => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SyncError {
+    fn from(e: serde_json::Error) -> SyncError {
+        SyncError::Parse(e)
+    }
+}
+
+/// What a sync of one list did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The amount of `New` client items sent to the server.
+    pub sent_new: usize,
+    /// The amount of `Removed` client items sent to the server.
+    pub sent_removed: usize,
+    /// The amount of items in the list after the sync.
+    pub items: usize,
+}
+
+/// The size and modification time of a server list right after it was synced, along with the number of items the
+/// client list had then. Used by [`sync_remote_all_incremental`] to find out if a list has changed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerSnapshot {
+    size: u64,
+    mtime: u64,
+    items: usize,
+}
+
+/// The [`ServerSnapshot`]s of the three lists synced by [`sync_remote_all_incremental`]. `None` means that the list
+/// is always transferred on the next sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncSnapshots {
+    /// The snapshot of the todos.
+    pub todos: Option<ServerSnapshot>,
+    /// The snapshot of the tasks.
+    pub tasks: Option<ServerSnapshot>,
+    /// The snapshot of the events.
+    pub events: Option<ServerSnapshot>,
+}
+
+/// The options of [`sync_remote_all_incremental`].
+pub struct SyncOptions<'a> {
+    /// Syncs every list fully and replaces the server lists with the client lists like in [`sync_remote`].
+    pub overwrite: bool,
+    /// The snapshots of the last sync, updated to the ones of this sync.
+    pub snapshots: &'a mut SyncSnapshots,
+    /// Called with a message for each step, such as every transfer with its size, for showing the progress or finding
+    /// out why a sync fails. Use `&mut |_| {}` to ignore them.
+    pub log: &'a mut dyn FnMut(&str),
+}
+
+/// The paths of the three lists on the server synced by [`sync_remote_all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerFiles {
+    /// The path of the todos.
+    pub todos: PathBuf,
+    /// The path of the tasks.
+    pub tasks: PathBuf,
+    /// The path of the events.
+    pub events: PathBuf,
+}
+
+impl ServerFiles {
+    /// Returns the paths of `todos.json`, `tasks.json` and `events.json` in the directory.
+    pub fn in_dir(dir: &Path) -> ServerFiles {
+        ServerFiles::with_prefix(dir, "")
+    }
+
+    /// Returns the paths of the lists in the directory with the prefix before each file name, such as
+    /// `alice-todos.json` with the prefix `alice-`. Users sharing a server directory can keep their lists apart this
+    /// way.
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use mtc::ServerFiles;
+    ///
+    /// let files = ServerFiles::with_prefix(Path::new("/srv/mtc"), "alice-");
+    ///
+    /// assert_eq!(files.todos, Path::new("/srv/mtc/alice-todos.json"));
+    /// ```
+    pub fn with_prefix(dir: &Path, prefix: &str) -> ServerFiles {
+        ServerFiles {
+            todos: dir.join(format!("{}todos.json", prefix)),
+            tasks: dir.join(format!("{}tasks.json", prefix)),
+            events: dir.join(format!("{}events.json", prefix)),
+        }
+    }
+}
 
 /// Synchronizes a client `MtcList` with a server `MtcList` on on a remote server using a given `&ssh2::Session`. ([ssh2](../ssh2/index.html) documents how to create a session.)
 /// The `server_path` should be a path to the saved `MtcList` on the server.
 /// Setting `overwrite` to true will result in the `client_list` being synced with itself
 /// with a copy of the list being sent to the server. If the server doesn't have a file yet then `overwrite` should be true.
+/// Otherwise [`SyncError::NotFound`] is returned.
+///
+/// The server list is saved sorted by `Ord` so that the same items always give the same file. The client list keeps
+/// its order, see [`MtcList::sync_self`] for how its ids change.
 pub fn sync_remote<T>(
     session: &Session,
     client_list: &mut MtcList<T>,
     server_path: &Path,
     overwrite: bool,
-) -> Result<(), Error>
+) -> Result<SyncReport, SyncError>
+where
+    T: MtcItem + Clone + Ord + DeserializeOwned + Serialize,
+{
+    sync_remote_with_limit(
+        session,
+        client_list,
+        server_path,
+        overwrite,
+        DEFAULT_SCP_SIZE_LIMIT,
+    )
+}
+
+/// Same as [`sync_remote`] but fails before uploading anything if the synced list is larger than `size_limit` bytes.
+/// Transfers are checked to be complete so a failed upload never leaves a truncated list silently on the server.
+///
+/// The lists are merged in a copy of `client_list` which replaces it only after the upload succeeded, so
+/// `client_list` is left as it was if the sync fails at any point.
+pub fn sync_remote_with_limit<T>(
+    session: &Session,
+    client_list: &mut MtcList<T>,
+    server_path: &Path,
+    overwrite: bool,
+    size_limit: u64,
+) -> Result<SyncReport, SyncError>
 where
-    T: MtcItem + Clone + DeserializeOwned + Serialize,
+    T: MtcItem + Clone + Ord + DeserializeOwned + Serialize,
 {
+    check_client(client_list)?;
+    let (sent_new, _, sent_removed) = client_list.state_counts();
+    // Merged in a copy since the states that tell what to send are cleared by the merge.
+    let mut merged = client_list.clone();
     let mut server_list;
     if overwrite {
-        client_list.sync_self();
-        server_list = client_list.clone_to_server();
+        merged.sync_self();
+        server_list = merged.clone_to_server();
     } else {
         let content = download_file(session, server_path)?;
         server_list = serde_json::from_str(&content)?;
-        client_list.sync(&mut server_list);
+        check_server(&server_list, server_path)?;
+        merged.sync(&mut server_list);
+    }
+
+    server_list.sort();
+    let content = serde_json::to_string(&server_list)?;
+    if content.len() as u64 > size_limit {
+        return Err(SyncError::Transfer(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The list is {} bytes which is over the scp limit of {} bytes. Use sync_remote_all which syncs over SFTP instead.",
+                content.len(),
+                size_limit
+            ),
+        )));
+    }
+    upload_file(session, server_path, &content)?;
+    *client_list = merged;
+    Ok(SyncReport {
+        sent_new,
+        sent_removed,
+        items: server_list.items().len(),
+    })
+}
+
+/// Synchronizes all three client lists with the server lists saved at the [`ServerFiles`], which are usually
+/// [`ServerFiles::in_dir`]. `overwrite` and a missing server file work like in [`sync_remote`].
+///
+/// Every call to `sync_remote` opens two scp channels, one for the download and one for the upload, which each cost
+/// a channel open and an exec round trip before any data is sent. This function opens a single SFTP channel and
+/// reuses it for all six transfers so syncing everything takes one channel setup instead of six.
+pub fn sync_remote_all(
+    session: &Session,
+    todos: &mut MtcList<Todo>,
+    tasks: &mut MtcList<Task>,
+    events: &mut MtcList<Event>,
+    files: &ServerFiles,
+    overwrite: bool,
+) -> Result<[SyncReport; 3], SyncError> {
+    sync_remote_all_incremental(
+        session,
+        todos,
+        tasks,
+        events,
+        files,
+        SyncOptions {
+            overwrite,
+            snapshots: &mut SyncSnapshots::default(),
+            log: &mut |_| {},
+        },
+    )
+}
+
+/// Same as [`sync_remote_all`] but skips the download and upload of a list when it hasn't changed since the last
+/// sync. The `snapshots` in the [`SyncOptions`] should be saved and given to the next call which updates them.
+///
+/// A list is skipped only if all of these hold:
+/// - `overwrite` in the [`SyncOptions`] is false.
+/// - The client list has no `New` or `Removed` items.
+/// - The client list isn't dirty, see [`MtcList::is_dirty`].
+/// - The client list has as many items as it had after the last sync.
+/// - The size and modification time of the server file equal the snapshot.
+///
+/// Otherwise the list is synced fully. Only the file's attributes are fetched for the check so an unchanged list
+/// costs one round trip instead of two transfers. Modification times have a resolution of one second so a list
+/// changed by another client within the same second and to the exact same size would be missed until it changes
+/// again. Syncing a client list in any other way, including with itself, should reset its snapshot to `None`.
+///
+/// The reports are for the todos, tasks and events in that order. A skipped list has a report with no sent items.
+pub fn sync_remote_all_incremental(
+    session: &Session,
+    todos: &mut MtcList<Todo>,
+    tasks: &mut MtcList<Task>,
+    events: &mut MtcList<Event>,
+    files: &ServerFiles,
+    options: SyncOptions,
+) -> Result<[SyncReport; 3], SyncError> {
+    let SyncOptions {
+        overwrite,
+        snapshots,
+        log,
+    } = options;
+    log("Opening an SFTP channel.");
+    let sftp = session.sftp().map_err(|e| SyncError::Connect(e.into()))?;
+
+    let todos = sync_sftp(
+        &sftp,
+        todos,
+        &files.todos,
+        overwrite,
+        &mut snapshots.todos,
+        log,
+    )?;
+    let tasks = sync_sftp(
+        &sftp,
+        tasks,
+        &files.tasks,
+        overwrite,
+        &mut snapshots.tasks,
+        log,
+    )?;
+    let events = sync_sftp(
+        &sftp,
+        events,
+        &files.events,
+        overwrite,
+        &mut snapshots.events,
+        log,
+    )?;
+    Ok([todos, tasks, events])
+}
+
+/// Appends a line to a log file on the server, creating the file if it doesn't exist. A newline is added to the line.
+///
+/// The file is opened in SFTP append mode and the line is sent in one write, so the server adds it to the end of
+/// the file even if other clients append at the same time. No client downloads and rewrites the file, so lines
+/// from concurrent clients are never lost.
+pub fn append_server_log(session: &Session, path: &Path, line: &str) -> Result<(), SyncError> {
+    let sftp = session.sftp().map_err(|e| SyncError::Connect(e.into()))?;
+    let mut file = sftp
+        .open_mode(
+            path,
+            OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE,
+            0o644,
+            OpenType::File,
+        )
+        .map_err(transfer_error)?;
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(transfer_error)
+}
+
+fn sync_sftp<T>(
+    sftp: &Sftp,
+    client_list: &mut MtcList<T>,
+    server_path: &Path,
+    overwrite: bool,
+    snapshot: &mut Option<ServerSnapshot>,
+    log: &mut dyn FnMut(&str),
+) -> Result<SyncReport, SyncError>
+where
+    T: MtcItem + Clone + Ord + DeserializeOwned + Serialize,
+{
+    check_client(client_list)?;
+    let (new, neutral, removed) = client_list.state_counts();
+    if !overwrite && new == 0 && removed == 0 && !client_list.is_dirty() && snapshot.is_some() {
+        let stat = sftp
+            .stat(server_path)
+            .map_err(|e| sftp_open_error(e, server_path))?;
+        if *snapshot == server_snapshot(&stat, neutral) {
+            log(&format!(
+                "{}: unchanged since the last sync, skipped.",
+                server_path.display()
+            ));
+            return Ok(SyncReport {
+                items: neutral,
+                ..SyncReport::default()
+            });
+        }
+    }
+    // Cleared first so that a failed sync is never skipped the next time.
+    *snapshot = None;
+
+    // Merged in a copy since the states that tell what to send are cleared by the merge.
+    let mut merged = client_list.clone();
+    let mut server_list;
+    if overwrite {
+        merged.sync_self();
+        server_list = merged.clone_to_server();
+        log(&format!(
+            "{}: overwriting with {} items.",
+            server_path.display(),
+            server_list.items().len()
+        ));
+    } else {
+        log(&format!("{}: downloading.", server_path.display()));
+        let mut file = sftp
+            .open(server_path)
+            .map_err(|e| sftp_open_error(e, server_path))?;
+        let mut content = String::new();
+        file.read_to_string(&mut content).map_err(transfer_error)?;
+        log(&format!(
+            "{}: downloaded {} bytes.",
+            server_path.display(),
+            content.len()
+        ));
+        server_list = serde_json::from_str(&content)?;
+        check_server(&server_list, server_path)?;
+        merged.sync(&mut server_list);
+        log(&format!(
+            "{}: merged {} new and {} removed items, {} items after the sync.",
+            server_path.display(),
+            new,
+            removed,
+            server_list.items().len()
+        ));
     }
 
-    upload_file(session, server_path, &serde_json::to_string(&server_list)?)
+    server_list.sort();
+    let content = serde_json::to_string(&server_list)?;
+    let stat = upload_sftp(sftp, server_path, &content)?;
+    log(&format!(
+        "{}: uploaded {} bytes.",
+        server_path.display(),
+        content.len()
+    ));
+
+    *snapshot = server_snapshot(&stat, merged.state_counts().1);
+    *client_list = merged;
+    Ok(SyncReport {
+        sent_new: new,
+        sent_removed: removed,
+        items: server_list.items().len(),
+    })
+}
+
+/// Uploads the content to a temporary file next to the path and renames it over the path once the whole content is
+/// written, so that an interrupted upload never leaves a truncated list for the other clients. Returns the attributes
+/// of the uploaded file.
+fn upload_sftp(sftp: &Sftp, server_path: &Path, content: &str) -> Result<FileStat, SyncError> {
+    let mut tmp_path = server_path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let written = sftp
+        .create(&tmp_path)
+        .map_err(transfer_error)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes()).map_err(transfer_error)?;
+            let stat = file.stat().map_err(transfer_error)?;
+            check_transfer_size(stat.size.unwrap_or(0), content.len() as u64, "upload")
+        });
+    if let Err(e) = written {
+        let _ = sftp.unlink(&tmp_path);
+        return Err(e);
+    }
+
+    let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC;
+    if let Err(e) = sftp.rename(&tmp_path, server_path, Some(flags)) {
+        // Servers speaking SFTP version 3, such as OpenSSH, don't overwrite when renaming. The whole list is already
+        // on the server so the old one is only missing for a moment. The old list is kept if the upload is gone.
+        sftp.stat(&tmp_path).map_err(|_| transfer_error(e))?;
+        sftp.unlink(server_path).map_err(transfer_error)?;
+        sftp.rename(&tmp_path, server_path, None)
+            .map_err(transfer_error)?;
+    }
+    sftp.stat(server_path).map_err(transfer_error)
 }
 
-fn download_file(session: &Session, remote_file_path: &Path) -> Result<String, Error> {
-    let (mut remote_file, _) = session.scp_recv(remote_file_path)?;
+/// Syncing panics if both lists are servers so a wrong client list is reported before anything is transferred.
+fn check_client<T: MtcItem + Clone>(client_list: &MtcList<T>) -> Result<(), SyncError> {
+    if client_list.is_server() {
+        return Err(SyncError::Merge(
+            "The client list is a server list. Convert it with clone_to_client first.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_server<T: MtcItem + Clone>(
+    server_list: &MtcList<T>,
+    path: &Path,
+) -> Result<(), SyncError> {
+    if !server_list.is_server() {
+        return Err(SyncError::Merge(format!(
+            "The list at '{}' is not a server list.",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+fn transfer_error<E: Into<Error>>(e: E) -> SyncError {
+    SyncError::Transfer(e.into())
+}
+
+fn server_snapshot(stat: &FileStat, items: usize) -> Option<ServerSnapshot> {
+    Some(ServerSnapshot {
+        size: stat.size?,
+        mtime: stat.mtime?,
+        items,
+    })
+}
+
+fn sftp_open_error(e: ssh2::Error, path: &Path) -> SyncError {
+    // Permission and other errors are returned as they are.
+    if e.code() == ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE) {
+        SyncError::NotFound(path.to_path_buf())
+    } else {
+        transfer_error(e)
+    }
+}
+
+fn download_file(session: &Session, remote_file_path: &Path) -> Result<String, SyncError> {
+    let (mut remote_file, stat) = session.scp_recv(remote_file_path).map_err(|e| {
+        // scp only reports the reason as the message of the remote scp process.
+        if e.message().contains("No such file or directory") {
+            SyncError::NotFound(remote_file_path.to_path_buf())
+        } else {
+            transfer_error(e)
+        }
+    })?;
     let mut content = String::new();
-    remote_file.read_to_string(&mut content)?;
+    remote_file
+        .read_to_string(&mut content)
+        .map_err(transfer_error)?;
 
-    remote_file.send_eof()?;
-    remote_file.wait_eof()?;
-    remote_file.close()?;
-    remote_file.wait_close()?;
+    remote_file.send_eof().map_err(transfer_error)?;
+    remote_file.wait_eof().map_err(transfer_error)?;
+    remote_file.close().map_err(transfer_error)?;
+    remote_file.wait_close().map_err(transfer_error)?;
 
+    check_transfer_size(content.len() as u64, stat.size(), "download")?;
     Ok(content)
 }
 
-fn upload_file(session: &Session, remote_file_path: &Path, content: &str) -> Result<(), Error> {
-    let mut remote_file =
-        session.scp_send(remote_file_path, 0o644, content.bytes().len() as u64, None)?;
-    remote_file.write(content.as_bytes())?;
+fn upload_file(session: &Session, remote_file_path: &Path, content: &str) -> Result<(), SyncError> {
+    let mut remote_file = session
+        .scp_send(remote_file_path, 0o644, content.len() as u64, None)
+        .map_err(transfer_error)?;
+    let written =
+        std::io::copy(&mut content.as_bytes(), &mut remote_file).map_err(transfer_error)?;
 
-    remote_file.send_eof()?;
-    remote_file.wait_eof()?;
-    remote_file.close()?;
-    remote_file.wait_close()?;
+    remote_file.send_eof().map_err(transfer_error)?;
+    remote_file.wait_eof().map_err(transfer_error)?;
+    remote_file.close().map_err(transfer_error)?;
+    remote_file.wait_close().map_err(transfer_error)?;
 
+    check_transfer_size(written, content.len() as u64, "upload")?;
+    // The remote scp exits with a non-zero status if it failed to write the whole file.
+    if remote_file.exit_status().map_err(transfer_error)? != 0 {
+        return Err(transfer_error(Error::new(
+            ErrorKind::Other,
+            "The server failed to save the uploaded list.",
+        )));
+    }
+    Ok(())
+}
+
+const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+
+fn check_transfer_size(transferred: u64, expected: u64, direction: &str) -> Result<(), SyncError> {
+    if transferred != expected {
+        return Err(transfer_error(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!(
+                "Incomplete {}: transferred {} of {} bytes.",
+                direction, transferred, expected
+            ),
+        )));
+    }
     Ok(())
 }